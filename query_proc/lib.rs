@@ -1,9 +1,83 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
-// use syn::{parse_macro_input, DeriveInput};
 
+mod query;
+mod system;
+mod system_data;
+
+/// Expands a compact query description into a full `Query<View, Filter>` type.
+///
+/// Elements are separated by commas:
+/// - `T` reads component `T`
+/// - `mut T` writes component `T`
+/// - `Entity` yields the entity id
+/// - `with T` requires `T` to be present without fetching it
+/// - `without T` requires `T` to be absent
+/// - `changed T` requires `T` to have changed since the system last ran
+/// - `added T` requires `T` to have been added since the system last ran
+///
+/// ```ignore
+/// type Data = (query!(mut Position, Velocity, with Frozen, without Dead, Entity),);
+/// ```
 #[proc_macro]
-pub fn query(_input: TokenStream) -> TokenStream {
-    //let input = syn::parse_macro_input!(input as syn::Expr);
-    "fn answer() -> u32 { 42 }".parse().unwrap()
+pub fn query(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as query::QueryInput);
+    input.expand().unwrap_or_else(|e| e.to_compile_error()).into()
+}
+
+/// Implements `SystemData` and `SetupData` for a struct whose fields are all `SystemData`.
+///
+/// Permissions of all fields are merged and a `<Name>Item<'w>` struct with the same
+/// field names is generated to hold the data fetched for a single run. Setup inserts
+/// default resources for fields implementing `SetupData` and skips the others.
+///
+/// ```ignore
+/// #[derive(Default, SystemData)]
+/// struct MovementData {
+///     positions: query!(mut Position, Velocity),
+///     time: Read<Time>,
+/// }
+/// ```
+#[proc_macro_derive(SystemData)]
+pub fn derive_system_data(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    system_data::derive(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Turns a function into a struct implementing `System` and a constructor returning its `SystemWrapper`.
+///
+/// Arguments are recognised as follows:
+/// - `#[resource] r: &T`, `&mut T`, `Option<&T>` or `Option<&mut T>` fetch resource `T`
+/// - `#[state] s: &mut T` is per-system state stored in `Local<T>`
+/// - `q: &mut Query<V, F>` or `q: &mut query!(..)` is a query
+/// - `&mut SubWorld` and `&mut CommandBuffer` are passed through
+///
+/// All arguments except `&mut SubWorld` and `&mut CommandBuffer` become one tuple of system data,
+/// which is only implemented up to 8 elements by `impl_data!`. Functions with more of them fail to
+/// compile because the tuple does not implement `SystemData`; group arguments with
+/// `#[derive(SystemData)]` or hand-written `System`s instead.
+///
+/// ```ignore
+/// #[system]
+/// fn update_positions(#[resource] time: &Time, query: &mut query!(mut Position, Velocity), world: &mut SubWorld) {}
+///
+/// let system: SystemWrapper<UpdatePositionsSystem> = update_positions_system();
+/// ```
+#[proc_macro_attribute]
+pub fn system(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "#[system] does not take arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let function = syn::parse_macro_input!(item as syn::ItemFn);
+    system::expand(function)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
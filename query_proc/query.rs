@@ -0,0 +1,279 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Ident, Token, Type};
+
+enum Access {
+    Read,
+    Write,
+    Entity,
+    With,
+    Without,
+    Changed,
+    Added,
+}
+
+struct Element {
+    access: Access,
+    ty: Type,
+}
+
+impl Parse for Element {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(Token![mut]) {
+            input.parse::<Token![mut]>()?;
+            let ty = input.parse()?;
+            if is_entity(&ty) {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    "`Entity` is always read, it cannot be `mut`",
+                ));
+            }
+            return Ok(Element { access: Access::Write, ty });
+        }
+
+        // Two identifiers in a row mean the first one is a keyword
+        if input.peek(Ident) && input.peek2(Ident) {
+            let keyword: Ident = input.parse()?;
+            let access = match keyword_access(&keyword) {
+                Some(access) => access,
+                None => {
+                    return Err(syn::Error::new(
+                        keyword.span(),
+                        format!(
+                            "unknown query keyword `{}`, expected `mut`, `with`, `without`, `changed` or `added`",
+                            keyword
+                        ),
+                    ))
+                }
+            };
+            let ty = input.parse()?;
+            return Ok(Element { access, ty });
+        }
+
+        // `with ::foo::Bar` reads like a keyword, but would otherwise parse as the path `with::foo::Bar`
+        if input.peek(Ident) && input.peek2(Token![::]) {
+            let fork = input.fork();
+            let keyword: Ident = fork.parse()?;
+            if keyword_access(&keyword).is_some() {
+                return Err(syn::Error::new(
+                    keyword.span(),
+                    format!(
+                        "`{}` must be followed by a type path without a leading `::`",
+                        keyword
+                    ),
+                ));
+            }
+        }
+
+        let ty: Type = input.parse()?;
+        let access = if is_entity(&ty) {
+            Access::Entity
+        } else {
+            Access::Read
+        };
+        Ok(Element { access, ty })
+    }
+}
+
+fn keyword_access(keyword: &Ident) -> Option<Access> {
+    match keyword.to_string().as_str() {
+        "with" => Some(Access::With),
+        "without" => Some(Access::Without),
+        "changed" => Some(Access::Changed),
+        "added" => Some(Access::Added),
+        _ => None,
+    }
+}
+
+fn is_entity(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident == "Entity" && segment.arguments.is_empty())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+pub struct QueryInput {
+    elements: Punctuated<Element, Token![,]>,
+}
+
+impl Parse for QueryInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(QueryInput {
+            elements: Punctuated::parse_terminated(input)?,
+        })
+    }
+}
+
+impl QueryInput {
+    pub fn expand(&self) -> syn::Result<TokenStream> {
+        self.check_duplicates()?;
+
+        let mut views = Vec::new();
+        let mut filters = Vec::new();
+        let mut dynamic_filters = Vec::new();
+
+        for element in &self.elements {
+            let ty = &element.ty;
+            match element.access {
+                Access::Read => {
+                    views.push(quote!(::legion::Read<#ty>));
+                    filters.push(quote!(::legion::query::ComponentFilter<#ty>));
+                }
+                Access::Write => {
+                    views.push(quote!(::legion::Write<#ty>));
+                    filters.push(quote!(::legion::query::ComponentFilter<#ty>));
+                }
+                Access::Entity => views.push(quote!(#ty)),
+                Access::With => filters.push(quote!(::legion::query::ComponentFilter<#ty>)),
+                Access::Without => filters.push(
+                    quote!(::legion::query::Not<::legion::query::ComponentFilter<#ty>>),
+                ),
+                Access::Changed => {
+                    filters.push(quote!(::legion::query::ComponentFilter<#ty>));
+                    dynamic_filters.push(quote!(crate::Changed<#ty>));
+                }
+                Access::Added => {
+                    filters.push(quote!(::legion::query::ComponentFilter<#ty>));
+                    dynamic_filters.push(quote!(crate::Added<#ty>));
+                }
+            }
+        }
+
+        let view = match views.len() {
+            0 => {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "query must fetch at least one component or `Entity`",
+                ))
+            }
+            1 => views.remove(0),
+            _ => quote!((#(#views),*)),
+        };
+
+        let layout = match filters.len() {
+            0 => quote!(::legion::query::Any),
+            1 => filters.remove(0),
+            _ => quote!(::legion::query::And<(#(#filters),*)>),
+        };
+
+        let dynamic = match dynamic_filters.len() {
+            0 => quote!(::legion::query::Passthrough),
+            1 => dynamic_filters.remove(0),
+            _ => quote!(::legion::query::And<(#(#dynamic_filters),*)>),
+        };
+
+        Ok(quote! {
+            ::legion::query::Query<
+                #view,
+                ::legion::query::EntityFilterTuple<#layout, #dynamic>
+            >
+        })
+    }
+
+    fn check_duplicates(&self) -> syn::Result<()> {
+        let mut seen = Vec::new();
+        let mut error: Option<syn::Error> = None;
+
+        // Fetched components and filters are checked separately, so `Position, changed Position` is fine
+        for element in &self.elements {
+            let fetched = matches!(
+                element.access,
+                Access::Read | Access::Write | Access::Entity
+            );
+            let key = (fetched, element.ty.to_token_stream().to_string());
+            if seen.contains(&key) {
+                let e = syn::Error::new_spanned(
+                    &element.ty,
+                    format!("`{}` appears more than once in query", key.1),
+                );
+                match &mut error {
+                    Some(error) => error.combine(e),
+                    None => error = Some(e),
+                }
+            } else {
+                seen.push(key);
+            }
+        }
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::QueryInput;
+    use quote::quote;
+
+    fn expand(input: &str) -> syn::Result<String> {
+        let input: QueryInput = syn::parse_str(input)?;
+        input.expand().map(|tokens| tokens.to_string())
+    }
+
+    fn error(input: &str) -> String {
+        expand(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn expands_views_and_filters() {
+        let expected = quote! {
+            ::legion::query::Query<
+                (::legion::Write<Position>, ::legion::Read<Velocity>),
+                ::legion::query::EntityFilterTuple<
+                    ::legion::query::And<(
+                        ::legion::query::ComponentFilter<Position>,
+                        ::legion::query::ComponentFilter<Velocity>,
+                        ::legion::query::Not<::legion::query::ComponentFilter<Frozen>>
+                    )>,
+                    ::legion::query::Passthrough
+                >
+            >
+        };
+        assert_eq!(
+            expand("mut Position, Velocity, without Frozen").unwrap(),
+            expected.to_string()
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keywords() {
+        assert_eq!(
+            error("within Position"),
+            "unknown query keyword `within`, expected `mut`, `with`, `without`, `changed` or `added`"
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_components() {
+        assert_eq!(
+            error("Position, mut Position"),
+            "`Position` appears more than once in query"
+        );
+        assert!(expand("Position, changed Position").is_ok());
+    }
+
+    #[test]
+    fn rejects_keywords_before_absolute_paths() {
+        assert_eq!(
+            error("Position, with ::foo::Bar"),
+            "`with` must be followed by a type path without a leading `::`"
+        );
+    }
+
+    #[test]
+    fn rejects_mutable_entity() {
+        assert_eq!(
+            error("mut Entity"),
+            "`Entity` is always read, it cannot be `mut`"
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{FnArg, GenericArgument, Ident, ItemFn, Pat, PathArguments, Type, TypeReference};
+
+enum Argument {
+    Data { data: TokenStream, pass: TokenStream },
+    World,
+    CommandBuffer,
+}
+
+fn last_ident(ty: &Type) -> Option<&Ident> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            path.path.segments.last().map(|segment| &segment.ident)
+        }
+        Type::Macro(mac) => mac.mac.path.segments.last().map(|segment| &segment.ident),
+        _ => None,
+    }
+}
+
+fn option_argument(ty: &Type) -> Option<&Type> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+            match arguments.args.first()? {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn reference(ty: &Type) -> Option<&TypeReference> {
+    match ty {
+        Type::Reference(reference) => Some(reference),
+        _ => None,
+    }
+}
+
+fn resource_argument(ty: &Type, binding: &Ident) -> syn::Result<Argument> {
+    let error = || {
+        syn::Error::new_spanned(
+            ty,
+            "resources must be taken as `&T`, `&mut T`, `Option<&T>` or `Option<&mut T>`",
+        )
+    };
+
+    let (reference, optional) = match option_argument(ty) {
+        Some(inner) => (reference(inner).ok_or_else(error)?, true),
+        None => (reference(ty).ok_or_else(error)?, false),
+    };
+    let elem = &reference.elem;
+
+    let (data, pass) = match (reference.mutability.is_some(), optional) {
+        (false, false) => (quote!(::legion::Read<#elem>), quote!(&*#binding)),
+        (true, false) => (quote!(::legion::Write<#elem>), quote!(&mut *#binding)),
+        (false, true) => (
+            quote!(::std::option::Option<::legion::Read<#elem>>),
+            quote!(#binding.as_deref()),
+        ),
+        (true, true) => (
+            quote!(::std::option::Option<::legion::Write<#elem>>),
+            quote!(#binding.as_deref_mut()),
+        ),
+    };
+
+    Ok(Argument::Data { data, pass })
+}
+
+fn state_argument(ty: &Type, binding: &Ident) -> syn::Result<Argument> {
+    let reference = reference(ty).ok_or_else(|| {
+        syn::Error::new_spanned(ty, "state must be taken as `&T` or `&mut T`")
+    })?;
+    let elem = &reference.elem;
+
+    let pass = if reference.mutability.is_some() {
+        quote!(#binding)
+    } else {
+        quote!(&*#binding)
+    };
+
+    Ok(Argument::Data {
+        data: quote!(crate::Local<#elem>),
+        pass,
+    })
+}
+
+fn plain_argument(ty: &Type, binding: &Ident) -> syn::Result<Argument> {
+    if let Some(reference) = reference(ty) {
+        let elem = &reference.elem;
+        let mutable = reference.mutability.is_some();
+
+        match last_ident(elem) {
+            Some(ident) if ident == "SubWorld" && mutable => return Ok(Argument::World),
+            Some(ident) if ident == "CommandBuffer" && mutable => {
+                return Ok(Argument::CommandBuffer)
+            }
+            Some(ident) if (ident == "Query" || ident == "query") && mutable => {
+                return Ok(Argument::Data {
+                    data: quote!(#elem),
+                    pass: quote!(#binding),
+                })
+            }
+            Some(ident) if ident == "Query" || ident == "query" => {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    "queries must be taken as `&mut Query<V, F>`",
+                ))
+            }
+            _ => {}
+        }
+    }
+
+    Err(syn::Error::new_spanned(
+        ty,
+        "unsupported system argument, expected `#[resource]`, `#[state]`, \
+         `&mut Query<V, F>`, `&mut SubWorld` or `&mut CommandBuffer`",
+    ))
+}
+
+fn to_pascal_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+pub fn expand(mut function: ItemFn) -> syn::Result<TokenStream> {
+    if !function.sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &function.sig.generics,
+            "systems cannot have generic parameters",
+        ));
+    }
+
+    let mut data = Vec::new();
+    let mut bindings = Vec::new();
+    let mut arguments = Vec::new();
+
+    for (index, input) in function.sig.inputs.iter_mut().enumerate() {
+        let input = match input {
+            FnArg::Typed(input) => input,
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(
+                    receiver,
+                    "systems cannot take `self`",
+                ))
+            }
+        };
+
+        let mut resource = false;
+        let mut state = false;
+        input.attrs.retain(|attr| {
+            if attr.path.is_ident("resource") {
+                resource = true;
+                false
+            } else if attr.path.is_ident("state") {
+                state = true;
+                false
+            } else {
+                true
+            }
+        });
+
+        if resource && state {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "an argument cannot be both `#[resource]` and `#[state]`",
+            ));
+        }
+
+        let binding = match &*input.pat {
+            Pat::Ident(pat) => format_ident!("__{}", pat.ident),
+            _ => format_ident!("__arg{}", index),
+        };
+
+        let argument = if resource {
+            resource_argument(&input.ty, &binding)?
+        } else if state {
+            state_argument(&input.ty, &binding)?
+        } else {
+            plain_argument(&input.ty, &binding)?
+        };
+
+        match argument {
+            Argument::Data { data: ty, pass } => {
+                data.push(ty);
+                bindings.push(binding);
+                arguments.push(pass);
+            }
+            Argument::World => arguments.push(quote!(world)),
+            Argument::CommandBuffer => arguments.push(quote!(command_buffer)),
+        }
+    }
+
+    let vis = &function.vis;
+    let name = &function.sig.ident;
+    let struct_name = Ident::new(
+        &format!("{}System", to_pascal_case(&name.to_string())),
+        Span::call_site(),
+    );
+    let data_name = format_ident!("{}Data", struct_name);
+    let constructor = format_ident!("{}_system", name);
+    let struct_doc = format!("System running [`{}`].", name);
+    let data_doc = format!("Data of [`{}`].", struct_name);
+    let constructor_doc = format!("Creates a wrapped system running [`{}`].", name);
+
+    Ok(quote! {
+        #function
+
+        #[doc = #struct_doc]
+        #vis struct #struct_name;
+
+        // Wraps the tuple of all arguments, so setup can skip resources without `Default` instead of
+        // requiring the tuple to implement `SetupData`.
+        #[doc = #data_doc]
+        #[derive(Default)]
+        #vis struct #data_name((#(#data,)*));
+
+        impl crate::SystemData for #data_name {
+            fn component_permissions() -> ::legion::world::Permissions<::legion::storage::ComponentTypeId> {
+                <(#(#data,)*) as crate::SystemData>::component_permissions()
+            }
+
+            fn resource_permissions() -> ::legion::world::Permissions<::legion::systems::ResourceTypeId> {
+                <(#(#data,)*) as crate::SystemData>::resource_permissions()
+            }
+
+            fn filter_archetypes(&mut self, world: &::legion::World, archetypes: &mut ::bit_set::BitSet) {
+                crate::SystemData::filter_archetypes(&mut self.0, world, archetypes);
+            }
+
+            fn parameters(parameters: &mut Vec<crate::ParameterAccess>) {
+                <(#(#data,)*) as crate::SystemData>::parameters(parameters);
+            }
+
+            fn validate(
+                system: &::legion::systems::SystemId,
+                resources: &::legion::Resources,
+                errors: &mut Vec<crate::SystemDataError>,
+            ) {
+                <(#(#data,)*) as crate::SystemData>::validate(system, resources, errors);
+            }
+        }
+
+        impl crate::SetupData for #data_name {
+            fn setup(world: &mut ::legion::World, resources: &mut ::legion::Resources) {
+                #[allow(unused_imports)]
+                use crate::{SetupWithDefaults as _, SetupWithoutDefaults as _};
+                #( (&crate::SetupProbe::<#data>::new()).setup(world, resources); )*
+            }
+        }
+
+        impl<'w> crate::SystemDataFetch<'w> for #data_name {
+            type Item = <(#(#data,)*) as crate::SystemDataFetch<'w>>::Item;
+
+            unsafe fn fetch_unchecked(&'w mut self, resources: &'w ::legion::Resources) -> Self::Item {
+                crate::SystemDataFetch::fetch_unchecked(&mut self.0, resources)
+            }
+        }
+
+        impl crate::System for #struct_name {
+            type Data = #data_name;
+
+            #[allow(unused_variables, unused_mut)]
+            fn run(
+                &mut self,
+                (#(mut #bindings,)*): crate::SystemDataItem<'_, Self::Data>,
+                command_buffer: &mut ::legion::systems::CommandBuffer,
+                world: &mut ::legion::world::SubWorld,
+            ) {
+                #name(#(#arguments),*)
+            }
+        }
+
+        #[doc = #constructor_doc]
+        #vis fn #constructor() -> crate::SystemWrapper<#struct_name> {
+            crate::SystemWrapper::new(#struct_name)
+        }
+    })
+}
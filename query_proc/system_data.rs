@@ -0,0 +1,203 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::{
+    Data, DeriveInput, Field, Fields, GenericArgument, GenericParam, PathArguments, Token, Type,
+};
+
+fn named_fields(input: &DeriveInput) -> syn::Result<&Punctuated<Field, Token![,]>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "SystemData can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "SystemData can only be derived for structs",
+            ))
+        }
+    };
+
+    if let Some(GenericParam::Lifetime(lifetime)) = input
+        .generics
+        .params
+        .iter()
+        .find(|param| matches!(param, GenericParam::Lifetime(_)))
+    {
+        return Err(syn::Error::new_spanned(
+            lifetime,
+            "SystemData structs own their state and cannot have lifetime parameters",
+        ));
+    }
+
+    Ok(fields)
+}
+
+/// Resource borrowed by a field, recognised from `Read<T>`, `Write<T>` and their `Option`s.
+fn resource_borrow(ty: &Type) -> Option<(String, bool)> {
+    let segment = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    let argument = match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+            match arguments.args.first()? {
+                GenericArgument::Type(ty) => ty,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    if segment.ident == "Read" {
+        Some((argument.to_token_stream().to_string(), false))
+    } else if segment.ident == "Write" {
+        Some((argument.to_token_stream().to_string(), true))
+    } else if segment.ident == "Option" {
+        resource_borrow(argument)
+    } else {
+        None
+    }
+}
+
+/// Rejects fields which borrow the same resource when at least one of them writes it.
+///
+/// This is best effort: resources are compared by their tokens, so `Read<A>` and `Write<crate::A>`
+/// or a type alias slip through, and component conflicts are not checked at all. Both are caught
+/// when the data is wrapped, by `SystemWrapper::try_named`.
+fn check_resource_conflicts(fields: &Punctuated<Field, Token![,]>) -> syn::Result<()> {
+    let mut borrows: Vec<(&Field, String, bool)> = Vec::new();
+
+    for field in fields {
+        let (resource, write) = match resource_borrow(&field.ty) {
+            Some(borrow) => borrow,
+            None => continue,
+        };
+
+        for (other, other_resource, other_write) in &borrows {
+            if *other_resource == resource && (write || *other_write) {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    format!(
+                        "`{}` borrows resource `{}` which is already borrowed by `{}`, \
+                         and at least one of them writes it",
+                        field.ident.as_ref().unwrap(),
+                        resource,
+                        other.ident.as_ref().unwrap()
+                    ),
+                ));
+            }
+        }
+
+        borrows.push((field, resource, write));
+    }
+
+    Ok(())
+}
+
+pub fn derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let fields = named_fields(input)?;
+    check_resource_conflicts(fields)?;
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let item_name = format_ident!("{}Item", name);
+
+    let names: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+    let name_strings: Vec<_> = fields
+        .iter()
+        .map(|field| field.ident.as_ref().unwrap().to_string())
+        .collect();
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let field_vis: Vec<_> = fields.iter().map(|field| &field.vis).collect();
+
+    let mut data_generics = input.generics.clone();
+    {
+        let where_clause = data_generics.make_where_clause();
+        for ty in &types {
+            where_clause
+                .predicates
+                .push(syn::parse_quote!(#ty: crate::SystemData));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = data_generics.split_for_impl();
+
+    let mut fetch_generics = input.generics.clone();
+    fetch_generics.params.insert(0, syn::parse_quote!('w));
+    {
+        let where_clause = fetch_generics.make_where_clause();
+        for ty in &types {
+            where_clause
+                .predicates
+                .push(syn::parse_quote!(#ty: crate::SystemDataFetch<'w>));
+        }
+    }
+    let (fetch_impl_generics, item_generics, fetch_where_clause) = fetch_generics.split_for_impl();
+
+    let item_doc = format!("Data fetched from [`{}`] for a single system run.", name);
+
+    Ok(quote! {
+        #[doc = #item_doc]
+        #vis struct #item_name #fetch_impl_generics #fetch_where_clause {
+            #( #field_vis #names: <#types as crate::SystemDataFetch<'w>>::Item, )*
+        }
+
+        impl #impl_generics crate::SystemData for #name #ty_generics #where_clause {
+            fn component_permissions() -> ::legion::world::Permissions<::legion::storage::ComponentTypeId> {
+                let mut permissions = ::legion::world::Permissions::default();
+                #( permissions.add(<#types as crate::SystemData>::component_permissions()); )*
+                permissions
+            }
+
+            fn resource_permissions() -> ::legion::world::Permissions<::legion::systems::ResourceTypeId> {
+                let mut permissions = ::legion::world::Permissions::default();
+                #( permissions.add(<#types as crate::SystemData>::resource_permissions()); )*
+                permissions
+            }
+
+            fn filter_archetypes(&mut self, world: &::legion::World, archetypes: &mut ::bit_set::BitSet) {
+                #( <#types as crate::SystemData>::filter_archetypes(&mut self.#names, world, archetypes); )*
+            }
+
+            fn parameters(parameters: &mut Vec<crate::ParameterAccess>) {
+                #( {
+                    let start = parameters.len();
+                    <#types as crate::SystemData>::parameters(parameters);
+                    crate::name_parameters(&mut parameters[start..], #name_strings);
+                } )*
+            }
+
+            fn validate(
+                system: &::legion::systems::SystemId,
+                resources: &::legion::Resources,
+                errors: &mut Vec<crate::SystemDataError>,
+            ) {
+                #( <#types as crate::SystemData>::validate(system, resources, errors); )*
+            }
+        }
+
+        impl #impl_generics crate::SetupData for #name #ty_generics #where_clause {
+            fn setup(world: &mut ::legion::World, resources: &mut ::legion::Resources) {
+                #[allow(unused_imports)]
+                use crate::{SetupWithDefaults as _, SetupWithoutDefaults as _};
+                #( (&crate::SetupProbe::<#types>::new()).setup(world, resources); )*
+            }
+        }
+
+        impl #fetch_impl_generics crate::SystemDataFetch<'w> for #name #ty_generics #fetch_where_clause {
+            type Item = #item_name #item_generics;
+
+            unsafe fn fetch_unchecked(&'w mut self, resources: &'w ::legion::Resources) -> Self::Item {
+                #item_name {
+                    #( #names: <#types as crate::SystemDataFetch<'w>>::fetch_unchecked(&mut self.#names, resources), )*
+                }
+            }
+        }
+    })
+}
@@ -0,0 +1,231 @@
+use legion::query::{
+    And, Any, ComponentFilter, EntityFilterTuple, Not, Or, Passthrough, TryComponentFilter,
+};
+use legion::storage::{Component, ComponentTypeId};
+use legion::systems::{ResourceTypeId, SystemId};
+use legion::world::Permissions;
+use std::borrow::Cow;
+
+/// Access declared by a single system parameter.
+#[derive(Debug, Clone)]
+pub struct ParameterAccess {
+    pub name: Cow<'static, str>,
+    pub resources: Permissions<ResourceTypeId>,
+    pub components: Permissions<ComponentTypeId>,
+    /// Components every archetype the parameter matches contains.
+    pub required: Vec<ComponentTypeId>,
+    /// Components no archetype the parameter matches contains.
+    pub excluded: Vec<ComponentTypeId>,
+}
+
+/// Names parameters collected from a struct field.
+///
+/// A single parameter takes the name of the field, nested parameters are prefixed with it.
+pub fn name_parameters(parameters: &mut [ParameterAccess], field: &'static str) {
+    if let [parameter] = parameters {
+        parameter.name = field.into();
+    } else {
+        for parameter in parameters {
+            parameter.name = format!("{}.{}", field, parameter.name).into();
+        }
+    }
+}
+
+/// Two parameters of the same system borrow the same data and at least one of them mutably.
+#[derive(Debug, Clone)]
+pub struct AccessConflict {
+    pub system: SystemId,
+    pub first: Cow<'static, str>,
+    pub second: Cow<'static, str>,
+}
+
+impl std::fmt::Display for AccessConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "system `{}` has conflicting access in parameters `{}` and `{}`",
+            self.system, self.first, self.second
+        )
+    }
+}
+
+impl std::error::Error for AccessConflict {}
+
+/// Returns `true` if one side writes something the other side reads or writes.
+pub fn permissions_conflict<T: PartialEq>(a: &Permissions<T>, b: &Permissions<T>) -> bool {
+    let overlaps = |writes: &[T], other: &Permissions<T>| {
+        writes
+            .iter()
+            .any(|item| other.reads().contains(item) || other.writes().contains(item))
+    };
+
+    overlaps(a.writes(), b) || overlaps(b.writes(), a)
+}
+
+/// Returns `true` if no archetype can match both parameters, because one of them requires a
+/// component the other one excludes.
+fn provably_disjoint(a: &ParameterAccess, b: &ParameterAccess) -> bool {
+    let excludes = |required: &[ComponentTypeId], excluded: &[ComponentTypeId]| {
+        required
+            .iter()
+            .any(|component| excluded.contains(component))
+    };
+
+    excludes(&a.required, &b.excluded) || excludes(&b.required, &a.excluded)
+}
+
+/// Checks the parameters of a system for conflicting resource and component borrows.
+///
+/// Parameters may access the same components mutably only if their filters prove that they never
+/// match the same archetype, e.g. `query!(mut Position, with Player)` and
+/// `query!(mut Position, without Player)`.
+pub fn check_parameters(
+    system: &SystemId,
+    parameters: &[ParameterAccess],
+) -> Result<(), AccessConflict> {
+    for (i, first) in parameters.iter().enumerate() {
+        for second in parameters.iter().skip(i + 1) {
+            let resources = permissions_conflict(&first.resources, &second.resources);
+            let components = permissions_conflict(&first.components, &second.components)
+                && !provably_disjoint(first, second);
+
+            if resources || components {
+                return Err(AccessConflict {
+                    system: system.clone(),
+                    first: first.name.clone(),
+                    second: second.name.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Layout filters which can tell components archetypes must or must not contain to match.
+///
+/// Filters which can't tell, like [`Or`] or [`TryComponentFilter`], report nothing and so never
+/// prove parameters disjoint.
+pub trait FilterComponents {
+    /// Adds components every matching archetype contains to `required`, and components no matching
+    /// archetype contains to `excluded`.
+    fn components(required: &mut Vec<ComponentTypeId>, excluded: &mut Vec<ComponentTypeId>);
+
+    /// Same as [`FilterComponents::components`] for the negation of this filter.
+    fn negated_components(
+        _required: &mut Vec<ComponentTypeId>,
+        _excluded: &mut Vec<ComponentTypeId>,
+    ) {
+    }
+}
+
+impl<T: Component> FilterComponents for ComponentFilter<T> {
+    fn components(required: &mut Vec<ComponentTypeId>, _excluded: &mut Vec<ComponentTypeId>) {
+        required.push(ComponentTypeId::of::<T>());
+    }
+
+    fn negated_components(
+        _required: &mut Vec<ComponentTypeId>,
+        excluded: &mut Vec<ComponentTypeId>,
+    ) {
+        excluded.push(ComponentTypeId::of::<T>());
+    }
+}
+
+impl<F: FilterComponents> FilterComponents for Not<F> {
+    fn components(required: &mut Vec<ComponentTypeId>, excluded: &mut Vec<ComponentTypeId>) {
+        F::negated_components(required, excluded);
+    }
+
+    fn negated_components(
+        required: &mut Vec<ComponentTypeId>,
+        excluded: &mut Vec<ComponentTypeId>,
+    ) {
+        F::components(required, excluded);
+    }
+}
+
+impl<T> FilterComponents for TryComponentFilter<T> {
+    fn components(_required: &mut Vec<ComponentTypeId>, _excluded: &mut Vec<ComponentTypeId>) {}
+}
+
+impl FilterComponents for Any {
+    fn components(_required: &mut Vec<ComponentTypeId>, _excluded: &mut Vec<ComponentTypeId>) {}
+}
+
+impl FilterComponents for Passthrough {
+    fn components(_required: &mut Vec<ComponentTypeId>, _excluded: &mut Vec<ComponentTypeId>) {}
+}
+
+impl<L: FilterComponents, D> FilterComponents for EntityFilterTuple<L, D> {
+    fn components(required: &mut Vec<ComponentTypeId>, excluded: &mut Vec<ComponentTypeId>) {
+        L::components(required, excluded);
+    }
+}
+
+macro_rules! impl_filter_components {
+    ( $( $ty:ident ),* ) => {
+        impl<$( $ty: FilterComponents ),*> FilterComponents for And<( $( $ty, )* )> {
+            fn components(required: &mut Vec<ComponentTypeId>, excluded: &mut Vec<ComponentTypeId>) {
+                $( $ty::components(required, excluded); )*
+            }
+        }
+
+        // Matches of an `Or` only share what all alternatives share, which we don't track.
+        impl<$( $ty: FilterComponents ),*> FilterComponents for Or<( $( $ty, )* )> {
+            fn components(_required: &mut Vec<ComponentTypeId>, _excluded: &mut Vec<ComponentTypeId>) {}
+
+            fn negated_components(required: &mut Vec<ComponentTypeId>, excluded: &mut Vec<ComponentTypeId>) {
+                $( $ty::negated_components(required, excluded); )*
+            }
+        }
+    };
+}
+
+impl_filter_components!(A);
+impl_filter_components!(A, B);
+impl_filter_components!(A, B, C);
+impl_filter_components!(A, B, C, D);
+impl_filter_components!(A, B, C, D, E);
+impl_filter_components!(A, B, C, D, E, F);
+impl_filter_components!(A, B, C, D, E, F, G);
+impl_filter_components!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::Probe;
+    use crate::{SetupData, SystemWrapper};
+    use legion::Entity;
+    use query_proc::query;
+
+    struct Position;
+    struct Player;
+
+    fn wrap<D: SetupData>() -> bool {
+        SystemWrapper::try_named("probe", Probe::<D>::new()).is_ok()
+    }
+
+    #[test]
+    fn overlapping_component_access_is_rejected() {
+        assert!(!wrap::<(query!(mut Position), query!(Position))>());
+        assert!(!wrap::<(
+            query!(mut Position, with Player),
+            query!(mut Position)
+        )>());
+    }
+
+    #[test]
+    fn disjoint_filters_allow_overlapping_access() {
+        assert!(wrap::<(
+            query!(mut Position, with Player),
+            query!(mut Position, without Player),
+        )>());
+        assert!(wrap::<(
+            query!(mut Position, Player),
+            query!(mut Position, without Player),
+        )>());
+        assert!(wrap::<(query!(Position), query!(Position))>());
+        // Filtering on a component doesn't borrow it.
+        assert!(wrap::<(query!(mut Position), query!(Entity, with Position))>());
+    }
+}
@@ -0,0 +1,248 @@
+use legion::query::{DynamicFilter, Fetch, FilterResult};
+use legion::storage::Component;
+use legion::world::WorldId;
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+thread_local! {
+    static LAST_RUN: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Component version recorded after the previous run of the system running on this thread.
+fn last_run() -> u64 {
+    LAST_RUN.with(|last_run| last_run.get())
+}
+
+/// Makes `last_run` visible to [`Changed`] and [`Added`] filters on this thread until dropped.
+pub struct LastRunGuard {
+    previous: u64,
+}
+
+impl LastRunGuard {
+    pub fn enter(last_run: u64) -> Self {
+        Self {
+            previous: LAST_RUN.with(|current| current.replace(last_run)),
+        }
+    }
+}
+
+impl Drop for LastRunGuard {
+    fn drop(&mut self) {
+        LAST_RUN.with(|current| current.set(self.previous));
+    }
+}
+
+/// Matches archetypes in which `T` was written or inserted since the system last ran.
+///
+/// Change detection works on archetype granularity, comparing the component versions tracked by
+/// legion against the version the running [`SystemWrapper`](crate::SystemWrapper) recorded after its
+/// previous run. Outside of wrapped systems, and in their first run, every archetype matches.
+pub struct Changed<T> {
+    since: u64,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> Default for Changed<T> {
+    fn default() -> Self {
+        Self {
+            since: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Changed<T> {
+    fn clone(&self) -> Self {
+        Self {
+            since: self.since,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Component> DynamicFilter for Changed<T> {
+    fn prepare(&mut self, _world: WorldId) {
+        self.since = last_run();
+    }
+
+    fn matches_archetype<F: Fetch>(&mut self, fetch: &F) -> FilterResult {
+        match fetch.version::<T>() {
+            Some(version) => FilterResult::Match(version > self.since),
+            None => FilterResult::Defer,
+        }
+    }
+}
+
+/// Matches archetypes which gained `T` components since the system last ran.
+///
+/// Like [`Changed`], this works on archetype granularity: an archetype which grew is yielded as a
+/// whole, including entities which were already in it. Archetypes are tracked in query iteration
+/// order, which legion keeps stable because new archetypes are only ever appended. Outside of
+/// wrapped systems, and in their first run, every archetype matches.
+pub struct Added<T> {
+    since: u64,
+    index: usize,
+    previous: Vec<usize>,
+    current: Vec<usize>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> Default for Added<T> {
+    fn default() -> Self {
+        Self {
+            since: 0,
+            index: 0,
+            previous: Vec::new(),
+            current: Vec::new(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Added<T> {
+    fn clone(&self) -> Self {
+        Self {
+            since: self.since,
+            index: self.index,
+            previous: self.previous.clone(),
+            current: self.current.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Component> DynamicFilter for Added<T> {
+    fn prepare(&mut self, _world: WorldId) {
+        // A query may be iterated several times within one run, all of them compare against the
+        // archetype sizes seen in the last successful run.
+        let last_run = last_run();
+        if last_run != self.since {
+            self.since = last_run;
+            self.previous = std::mem::take(&mut self.current);
+        }
+        self.index = 0;
+    }
+
+    fn matches_archetype<F: Fetch>(&mut self, fetch: &F) -> FilterResult {
+        let len = match fetch.find::<T>() {
+            Some(components) => components.len(),
+            None => return FilterResult::Defer,
+        };
+
+        let index = self.index;
+        self.index += 1;
+
+        if index < self.current.len() {
+            self.current[index] = len;
+        } else {
+            self.current.push(len);
+        }
+
+        let added = self
+            .previous
+            .get(index)
+            .is_none_or(|&previous| len > previous);
+        FilterResult::Match(added)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ScheduleBuilder, System, SystemDataItem};
+    use legion::systems::CommandBuffer;
+    use legion::world::SubWorld;
+    use legion::{Resources, Universe};
+    use query_proc::query;
+    use std::sync::{Arc, Mutex};
+
+    struct Position(f32);
+    struct Frozen;
+
+    struct MoveChanged {
+        matched: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl System for MoveChanged {
+        type Data = (query!(mut Position, changed Position),);
+
+        fn run(
+            &mut self,
+            (query,): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            world: &mut SubWorld,
+        ) {
+            let mut matched = 0;
+            for position in query.iter_mut(world) {
+                position.0 += 1.0;
+                matched += 1;
+            }
+            self.matched.lock().unwrap().push(matched);
+        }
+    }
+
+    struct CountAdded {
+        matched: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl System for CountAdded {
+        type Data = (query!(Position, added Position),);
+
+        fn run(
+            &mut self,
+            (query,): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            world: &mut SubWorld,
+        ) {
+            let matched = query.iter(world).count();
+            self.matched.lock().unwrap().push(matched);
+        }
+    }
+
+    #[test]
+    fn changed_matches_writes_since_last_run() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+
+        world.extend(vec![(Position(0.0),), (Position(1.0),)]);
+        world.push((Position(2.0), Frozen));
+
+        let matched = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = ScheduleBuilder::new()
+            .add_data_system(MoveChanged {
+                matched: matched.clone(),
+            })
+            .build();
+
+        schedule.execute(&mut world, &mut resources);
+        // Writes of the previous run are not reported again.
+        schedule.execute(&mut world, &mut resources);
+        world.push((Position(3.0),));
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(*matched.lock().unwrap(), vec![3, 0, 3]);
+    }
+
+    #[test]
+    fn added_matches_insertions_since_last_run() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+
+        world.extend(vec![(Position(0.0),), (Position(1.0),)]);
+
+        let matched = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = ScheduleBuilder::new()
+            .add_data_system(CountAdded {
+                matched: matched.clone(),
+            })
+            .build();
+
+        schedule.execute(&mut world, &mut resources);
+        schedule.execute(&mut world, &mut resources);
+        // Lands in a new archetype, the two entities inserted before the first run are not yielded again.
+        world.push((Position(2.0), Frozen));
+        schedule.execute(&mut world, &mut resources);
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(*matched.lock().unwrap(), vec![2, 0, 1, 0]);
+    }
+}
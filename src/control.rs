@@ -0,0 +1,136 @@
+use legion::systems::SystemId;
+use legion::{Resources, World};
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Resource for enabling and disabling wrapped systems by name.
+///
+/// Changes requested through `&self` are queued and only take effect once [`SystemControl::apply`]
+/// runs, so every system sees the same set of disabled systems for a whole frame. Schedules built by
+/// [`ScheduleBuilder`](crate::ScheduleBuilder) apply queued changes at the start of every frame.
+/// Schedules built with legion's `Schedule::builder()` never apply them, unless
+/// [`apply_system_control`] is added to them as a thread-local function.
+/// Wrapped systems declare a read of it to check whether they are enabled.
+#[derive(Debug, Default)]
+pub struct SystemControl {
+    disabled: HashSet<SystemId>,
+    pending: Mutex<Vec<(SystemId, bool)>>,
+}
+
+impl SystemControl {
+    /// Returns `true` unless the system was disabled by a previous [`SystemControl::apply`].
+    pub fn is_enabled(&self, system: &SystemId) -> bool {
+        !self.disabled.contains(system)
+    }
+
+    /// Enables or disables the system starting with the next frame.
+    pub fn set_enabled<N: Into<SystemId>>(&self, system: N, enabled: bool) {
+        self.pending.lock().unwrap().push((system.into(), enabled));
+    }
+
+    pub fn disable<N: Into<SystemId>>(&self, system: N) {
+        self.set_enabled(system, false);
+    }
+
+    /// Toggles the system starting with the next frame, based on its state in the current frame.
+    pub fn toggle<N: Into<SystemId>>(&self, system: N) {
+        let system = system.into();
+        let enabled = self.is_enabled(&system);
+        self.set_enabled(system, !enabled);
+    }
+
+    /// Applies all queued changes in the order they were requested.
+    pub fn apply(&mut self) {
+        for (system, enabled) in self.pending.get_mut().unwrap().drain(..) {
+            if enabled {
+                self.disabled.remove(&system);
+            } else {
+                self.disabled.insert(system);
+            }
+        }
+    }
+}
+
+/// Applies queued [`SystemControl`] changes, if the resource is present.
+pub fn apply_system_control(_world: &mut World, resources: &mut Resources) {
+    if let Some(mut control) = resources.get_mut::<SystemControl>() {
+        control.apply();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SystemControl;
+    use crate::{ScheduleBuilder, System, SystemDataItem};
+    use legion::systems::CommandBuffer;
+    use legion::world::SubWorld;
+    use legion::{Read, Resources, Universe, Write};
+
+    #[derive(Default)]
+    struct Runs(u32);
+
+    struct Count;
+
+    impl System for Count {
+        type Data = (Write<Runs>,);
+
+        fn run(
+            &mut self,
+            (mut runs,): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            runs.0 += 1;
+        }
+    }
+
+    /// Makes `Toggler` toggle `control_test::count` once.
+    #[derive(Default)]
+    struct Toggle(bool);
+
+    struct Toggler;
+
+    impl System for Toggler {
+        type Data = (Read<SystemControl>, Write<Toggle>);
+
+        fn run(
+            &mut self,
+            (control, mut toggle): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            if std::mem::take(&mut toggle.0) {
+                control.toggle("control_test::count");
+            }
+        }
+    }
+
+    #[test]
+    fn toggles_apply_from_next_frame() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(SystemControl::default());
+        resources.insert(Runs::default());
+        resources.insert(Toggle::default());
+
+        let mut schedule = ScheduleBuilder::new()
+            .add_data_system_named("control_test::toggler", Toggler)
+            .flush()
+            .add_data_system_named("control_test::count", Count)
+            .build();
+        let mut frame = |resources: &mut Resources, toggle: bool| {
+            resources.get_mut::<Toggle>().unwrap().0 = toggle;
+            schedule.execute(&mut world, resources);
+            resources.get::<Runs>().unwrap().0
+        };
+
+        assert_eq!(frame(&mut resources, false), 1);
+        // Disabled while the frame requesting it is already running.
+        assert_eq!(frame(&mut resources, true), 2);
+        assert_eq!(frame(&mut resources, false), 2);
+        assert_eq!(frame(&mut resources, false), 2);
+        // Enabled again the same way.
+        assert_eq!(frame(&mut resources, true), 2);
+        assert_eq!(frame(&mut resources, false), 3);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{SetupData, System, SystemData, SystemDataError, SystemDataFetch};
+use legion::systems::{
+    CommandBuffer, Fetch, FetchMut, Resource, ResourceSet, ResourceTypeId, SystemId,
+};
+use legion::world::{Permissions, SubWorld};
+use legion::{Read, Resources, World, Write};
+use std::marker::PhantomData;
+
+/// Double buffered queue of events of type `E`.
+///
+/// Events stay available for two calls of [`Events::update`], so every reader gets at least
+/// one full frame to observe them regardless of system order.
+pub struct Events<E> {
+    previous: Vec<E>,
+    current: Vec<E>,
+    // Sequence number of the first event in each buffer
+    previous_start: usize,
+    current_start: usize,
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            previous_start: 0,
+            current_start: 0,
+        }
+    }
+}
+
+impl<E> Events<E> {
+    pub fn send(&mut self, event: E) {
+        self.current.push(event);
+    }
+
+    /// Drops events sent before the previous update and starts a new buffer.
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+        self.previous_start = self.current_start;
+        self.current_start += self.previous.len();
+    }
+
+    /// Sequence number of the next event to be sent.
+    fn end(&self) -> usize {
+        self.current_start + self.current.len()
+    }
+
+    /// Iterates over all stored events starting at sequence number `start`.
+    fn iter_from(&self, start: usize) -> impl Iterator<Item = &E> {
+        let previous = start
+            .saturating_sub(self.previous_start)
+            .min(self.previous.len());
+        let current = start
+            .saturating_sub(self.current_start)
+            .min(self.current.len());
+
+        self.previous[previous..]
+            .iter()
+            .chain(self.current[current..].iter())
+    }
+}
+
+/// Sends events of type `E`.
+pub struct EventWriter<E>(PhantomData<E>);
+
+impl<E> Default for EventWriter<E> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E> SystemData for EventWriter<E>
+where
+    E: Resource,
+{
+    fn resource_permissions() -> Permissions<ResourceTypeId> {
+        <Write<Events<E>> as SystemData>::resource_permissions()
+    }
+
+    fn validate(system: &SystemId, resources: &Resources, errors: &mut Vec<SystemDataError>) {
+        <Write<Events<E>> as SystemData>::validate(system, resources, errors);
+    }
+}
+
+impl<E> SetupData for EventWriter<E>
+where
+    E: Resource,
+{
+    fn setup(world: &mut World, resources: &mut Resources) {
+        <Write<Events<E>> as SetupData>::setup(world, resources);
+    }
+}
+
+impl<'w, E> SystemDataFetch<'w> for EventWriter<E>
+where
+    E: Resource,
+{
+    type Item = WriteEvents<'w, E>;
+
+    unsafe fn fetch_unchecked(&'w mut self, resources: &'w Resources) -> Self::Item {
+        WriteEvents {
+            events: <Write<Events<E>> as ResourceSet<'w>>::fetch_unchecked(resources),
+        }
+    }
+}
+
+/// Fetched [`EventWriter`].
+pub struct WriteEvents<'w, E: Resource> {
+    events: FetchMut<'w, Events<E>>,
+}
+
+impl<'w, E: Resource> WriteEvents<'w, E> {
+    pub fn send(&mut self, event: E) {
+        self.events.send(event);
+    }
+}
+
+/// Receives events of type `E`.
+///
+/// Each reader keeps its own cursor, so every event is observed exactly once per reader.
+pub struct EventReader<E> {
+    cursor: usize,
+    _phantom: PhantomData<E>,
+}
+
+impl<E> Default for EventReader<E> {
+    fn default() -> Self {
+        Self {
+            cursor: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<E> SystemData for EventReader<E>
+where
+    E: Resource,
+{
+    fn resource_permissions() -> Permissions<ResourceTypeId> {
+        <Read<Events<E>> as SystemData>::resource_permissions()
+    }
+
+    fn validate(system: &SystemId, resources: &Resources, errors: &mut Vec<SystemDataError>) {
+        <Read<Events<E>> as SystemData>::validate(system, resources, errors);
+    }
+}
+
+impl<E> SetupData for EventReader<E>
+where
+    E: Resource,
+{
+    fn setup(world: &mut World, resources: &mut Resources) {
+        <Read<Events<E>> as SetupData>::setup(world, resources);
+    }
+}
+
+impl<'w, E> SystemDataFetch<'w> for EventReader<E>
+where
+    E: Resource,
+{
+    type Item = ReadEvents<'w, E>;
+
+    unsafe fn fetch_unchecked(&'w mut self, resources: &'w Resources) -> Self::Item {
+        ReadEvents {
+            cursor: &mut self.cursor,
+            events: <Read<Events<E>> as ResourceSet<'w>>::fetch_unchecked(resources),
+        }
+    }
+}
+
+/// Fetched [`EventReader`].
+pub struct ReadEvents<'w, E: Resource> {
+    cursor: &'w mut usize,
+    events: Fetch<'w, Events<E>>,
+}
+
+impl<'w, E: Resource> ReadEvents<'w, E> {
+    /// Iterates over events which this reader has not seen yet and marks them as read.
+    pub fn iter(&mut self) -> impl Iterator<Item = &E> {
+        let start = *self.cursor;
+        *self.cursor = self.events.end();
+        self.events.iter_from(start)
+    }
+}
+
+/// Calls [`Events::update`] once per run, usually at the end of a frame.
+pub struct UpdateEvents<E>(PhantomData<E>);
+
+impl<E> UpdateEvents<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E> Default for UpdateEvents<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> System for UpdateEvents<E>
+where
+    E: Resource,
+{
+    type Data = Write<Events<E>>;
+
+    fn run(
+        &mut self,
+        mut events: FetchMut<Events<E>>,
+        _command_buffer: &mut CommandBuffer,
+        _world: &mut SubWorld,
+    ) {
+        events.update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventReader, EventWriter, UpdateEvents};
+    use crate::{ScheduleBuilder, System, SystemDataItem};
+    use legion::systems::CommandBuffer;
+    use legion::world::SubWorld;
+    use legion::{Resources, Universe};
+    use std::sync::{Arc, Mutex};
+
+    struct Hit(u32);
+
+    /// Reports two hits in each of the first three frames.
+    struct Collide {
+        frame: u32,
+    }
+
+    impl System for Collide {
+        type Data = EventWriter<Hit>;
+
+        fn run(
+            &mut self,
+            mut hits: SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            if self.frame < 3 {
+                hits.send(Hit(self.frame * 10));
+                hits.send(Hit(self.frame * 10 + 1));
+            }
+            self.frame += 1;
+        }
+    }
+
+    struct Damage {
+        received: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl System for Damage {
+        type Data = EventReader<Hit>;
+
+        fn run(
+            &mut self,
+            mut hits: SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            let mut received = self.received.lock().unwrap();
+            received.extend(hits.iter().map(|hit| hit.0));
+        }
+    }
+
+    #[test]
+    fn every_reader_receives_each_hit_once() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+
+        let before = Arc::new(Mutex::new(Vec::new()));
+        let after = Arc::new(Mutex::new(Vec::new()));
+
+        // Conflicting systems run in the order they are added, so the first reader only sees the
+        // hits of a frame in the next one.
+        let mut schedule = ScheduleBuilder::new()
+            .add_data_system_named(
+                "damage_before",
+                Damage {
+                    received: before.clone(),
+                },
+            )
+            .add_data_system_named("collide", Collide { frame: 0 })
+            .add_data_system_named(
+                "damage_after",
+                Damage {
+                    received: after.clone(),
+                },
+            )
+            .add_data_system_named("update_hits", UpdateEvents::<Hit>::default())
+            .setup(&mut world, &mut resources)
+            .build();
+
+        for _ in 0..5 {
+            schedule.execute(&mut world, &mut resources);
+        }
+
+        let expected = vec![0, 1, 10, 11, 20, 21];
+        assert_eq!(*before.lock().unwrap(), expected);
+        assert_eq!(*after.lock().unwrap(), expected);
+    }
+}
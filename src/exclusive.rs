@@ -0,0 +1,159 @@
+use crate::{SystemControl, SystemStats, TraceRecorder};
+use legion::systems::SystemId;
+use legion::{Resources, World};
+use std::time::Instant;
+
+/// A system with structural access to the whole world and all resources.
+///
+/// Exclusive systems act as a barrier, pending command buffers are flushed before they run
+/// and no other system runs concurrently with them.
+pub trait ExclusiveSystem {
+    fn run(&mut self, world: &mut World, resources: &mut Resources);
+}
+
+impl<F> ExclusiveSystem for F
+where
+    F: FnMut(&mut World, &mut Resources),
+{
+    fn run(&mut self, world: &mut World, resources: &mut Resources) {
+        self(world, resources)
+    }
+}
+
+/// Named [`ExclusiveSystem`] which records into [`SystemStats`] and [`TraceRecorder`].
+///
+/// Like wrapped systems, it is skipped while disabled through [`SystemControl`].
+pub struct ExclusiveWrapper<S> {
+    name: SystemId,
+    system: S,
+}
+
+impl<S> ExclusiveWrapper<S>
+where
+    S: ExclusiveSystem,
+{
+    pub fn named<N: Into<SystemId>>(name: N, system: S) -> Self {
+        Self {
+            name: name.into(),
+            system,
+        }
+    }
+
+    pub fn name(&self) -> &SystemId {
+        &self.name
+    }
+
+    pub fn run(&mut self, world: &mut World, resources: &mut Resources) {
+        let enabled = resources
+            .get::<SystemControl>()
+            .is_none_or(|control| control.is_enabled(&self.name));
+        if !enabled {
+            if let Some(stats) = resources.get::<SystemStats>() {
+                stats.record_skipped(&self.name);
+            }
+            return;
+        }
+
+        let start = Instant::now();
+        self.system.run(world, resources);
+        let duration = start.elapsed();
+
+        if let Some(stats) = resources.get::<SystemStats>() {
+            stats.record(&self.name, duration, 0);
+        }
+        if let Some(trace) = resources.get::<TraceRecorder>() {
+            trace.record(&self.name, start, duration);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ScheduleBuilder, System, SystemControl, SystemDataItem};
+    use legion::systems::CommandBuffer;
+    use legion::world::SubWorld;
+    use legion::{Resources, Universe, World};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    static RUNNING: AtomicUsize = AtomicUsize::new(0);
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+    static EXCLUSIVE: AtomicBool = AtomicBool::new(false);
+
+    struct Probe;
+
+    impl System for Probe {
+        type Data = ();
+
+        fn run(
+            &mut self,
+            _data: SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            assert!(!EXCLUSIVE.load(Ordering::SeqCst));
+            RUNNING.fetch_add(1, Ordering::SeqCst);
+            // Gives systems on the other side of the exclusive system a chance to overlap
+            std::thread::sleep(Duration::from_millis(5));
+            RUNNING.fetch_sub(1, Ordering::SeqCst);
+            RUNS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn exclusive_system_never_overlaps_wrapped_systems() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+
+        let mut schedule = ScheduleBuilder::new()
+            .add_data_system_named("exclusive_test::before_a", Probe)
+            .add_data_system_named("exclusive_test::before_b", Probe)
+            .add_exclusive_system_named(
+                "exclusive_test::exclusive",
+                |_world: &mut World, _resources: &mut Resources| {
+                    EXCLUSIVE.store(true, Ordering::SeqCst);
+                    assert_eq!(RUNNING.load(Ordering::SeqCst), 0);
+                    std::thread::sleep(Duration::from_millis(5));
+                    assert_eq!(RUNNING.load(Ordering::SeqCst), 0);
+                    EXCLUSIVE.store(false, Ordering::SeqCst);
+                },
+            )
+            .add_data_system_named("exclusive_test::after_a", Probe)
+            .add_data_system_named("exclusive_test::after_b", Probe)
+            .build();
+
+        for _ in 0..3 {
+            schedule.execute(&mut world, &mut resources);
+        }
+
+        assert_eq!(RUNS.load(Ordering::SeqCst), 12);
+    }
+
+    #[test]
+    fn disabled_exclusive_system_is_skipped_from_next_frame() {
+        static EXCLUSIVE_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(SystemControl::default());
+
+        let mut schedule = ScheduleBuilder::new()
+            .add_exclusive_system_named(
+                "exclusive_test::counted",
+                |_world: &mut World, _resources: &mut Resources| {
+                    EXCLUSIVE_RUNS.fetch_add(1, Ordering::SeqCst);
+                },
+            )
+            .build();
+
+        schedule.execute(&mut world, &mut resources);
+        resources
+            .get::<SystemControl>()
+            .unwrap()
+            .disable("exclusive_test::counted");
+        schedule.execute(&mut world, &mut resources);
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(EXCLUSIVE_RUNS.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,126 @@
+use legion::systems::SystemId;
+use std::any::Any;
+use std::sync::Mutex;
+
+/// What a wrapped system does when its [`System::run`](crate::System::run) panics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// The panic unwinds through the schedule, this is the default.
+    #[default]
+    Propagate,
+    /// The panic is caught and recorded into [`SystemFailures`], the system keeps running in later frames.
+    Catch,
+    /// Like `Catch`, but the system is also disabled through [`SystemControl`](crate::SystemControl),
+    /// starting with the next frame. It can be enabled again like any other disabled system.
+    CatchAndDisable,
+}
+
+/// A caught panic of a wrapped system.
+#[derive(Debug, Clone)]
+pub struct SystemFailure {
+    pub system: SystemId,
+    pub message: String,
+}
+
+impl std::fmt::Display for SystemFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "system `{}` panicked: {}", self.system, self.message)
+    }
+}
+
+/// Resource collecting panics caught by systems with a catching [`PanicPolicy`].
+///
+/// Panics are still caught when it is missing, they are just not collected. Collection happens through
+/// `&self`, which wrapped systems reach through a read they declare.
+#[derive(Debug, Default)]
+pub struct SystemFailures {
+    failures: Mutex<Vec<SystemFailure>>,
+}
+
+impl SystemFailures {
+    /// Removes and returns all failures recorded so far.
+    pub fn take(&self) -> Vec<SystemFailure> {
+        std::mem::take(&mut *self.failures.lock().unwrap())
+    }
+
+    pub fn record(&self, system: &SystemId, message: String) {
+        self.failures.lock().unwrap().push(SystemFailure {
+            system: system.clone(),
+            message,
+        });
+    }
+}
+
+/// Extracts the message of a panic payload.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&'static str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PanicPolicy, SystemFailures};
+    use crate::{ScheduleBuilder, System, SystemDataItem, SystemStats, SystemWrapper};
+    use legion::systems::CommandBuffer;
+    use legion::world::SubWorld;
+    use legion::{Resources, Universe};
+    use std::collections::HashMap;
+
+    struct Marker;
+
+    struct QueueThenPanic;
+
+    impl System for QueueThenPanic {
+        type Data = ();
+
+        fn run(
+            &mut self,
+            _data: SystemDataItem<'_, Self::Data>,
+            command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            command_buffer.push((Marker,));
+            panic!("queued one entity");
+        }
+    }
+
+    #[test]
+    fn catch_and_disable_discards_commands_and_skips_next_frame() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(SystemFailures::default());
+        resources.insert(SystemStats::default());
+
+        let mut builder = ScheduleBuilder::new();
+        builder.add_system(
+            SystemWrapper::named("failure_test::panics", QueueThenPanic)
+                .on_panic(PanicPolicy::CatchAndDisable),
+        );
+        builder.setup(&mut world, &mut resources);
+        let mut schedule = builder.build();
+
+        schedule.execute(&mut world, &mut resources);
+        schedule.execute(&mut world, &mut resources);
+
+        assert!(world.is_empty());
+
+        let failures = resources.get::<SystemFailures>().unwrap().take();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].system, "failure_test::panics".into());
+        assert_eq!(failures[0].message, "queued one entity");
+
+        let stats: HashMap<_, _> = resources
+            .get::<SystemStats>()
+            .unwrap()
+            .snapshot()
+            .into_iter()
+            .collect();
+        let stat = &stats[&"failure_test::panics".into()];
+        assert_eq!((stat.runs, stat.failures, stat.skipped), (1, 1, 1));
+    }
+}
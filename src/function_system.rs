@@ -0,0 +1,214 @@
+use crate::{
+    EventReader, EventWriter, FilterComponents, NonSend, NonSendMut, NonSendRef, NonSendRefMut,
+    ReadEvents, SetupData, System, SystemDataItem, SystemWrapper, WriteEvents,
+};
+use legion::query::{EntityFilter, Query, View};
+use legion::systems::{CommandBuffer, Fetch, FetchMut, Resource, SystemId};
+use legion::world::SubWorld;
+use legion::{Read, Write};
+use std::marker::PhantomData;
+
+/// Parameter of a function system, mapped to the [`SystemData`](crate::SystemData) which fetches it.
+///
+/// Per-system state is not a parameter, closures can simply capture it instead. Resources taken by
+/// `Fetch<T>` or `FetchMut<T>` are inserted during setup, so they require `T: Default`. Resources
+/// without a `Default` value can be taken as `Option`.
+pub trait SystemParam {
+    type Data: SetupData;
+}
+
+/// Value of parameter `P` fetched for a single run.
+pub type SystemParamItem<'w, P> = SystemDataItem<'w, <P as SystemParam>::Data>;
+
+impl<'a, T: Resource + Default> SystemParam for Fetch<'a, T> {
+    type Data = Read<T>;
+}
+
+impl<'a, T: Resource + Default> SystemParam for FetchMut<'a, T> {
+    type Data = Write<T>;
+}
+
+impl<'a, T: Resource> SystemParam for Option<Fetch<'a, T>> {
+    type Data = Option<Read<T>>;
+}
+
+impl<'a, T: Resource> SystemParam for Option<FetchMut<'a, T>> {
+    type Data = Option<Write<T>>;
+}
+
+impl<V, F> SystemParam for &mut Query<V, F>
+where
+    V: for<'b> View<'b>,
+    F: 'static + EntityFilter + FilterComponents,
+{
+    type Data = Query<V, F>;
+}
+
+impl<'a, E: Resource> SystemParam for WriteEvents<'a, E> {
+    type Data = EventWriter<E>;
+}
+
+impl<'a, E: Resource> SystemParam for ReadEvents<'a, E> {
+    type Data = EventReader<E>;
+}
+
+impl<'a, T: Default + 'static> SystemParam for NonSendRef<'a, T> {
+    type Data = NonSend<T>;
+}
+
+impl<'a, T: Default + 'static> SystemParam for NonSendRefMut<'a, T> {
+    type Data = NonSendMut<T>;
+}
+
+/// A [`System`] which calls a function with its fetched parameters.
+pub struct FunctionSystem<F, P> {
+    function: F,
+    _params: PhantomData<fn() -> P>,
+}
+
+/// Conversion of functions and closures into wrapped systems.
+///
+/// Accepts `FnMut(&mut SubWorld, P1, P2, ..)` where every parameter implements [`SystemParam`],
+/// e.g. `Fetch<T>`, `FetchMut<T>` or `&mut Query<V, F>`. Closures need explicit parameter types.
+pub trait IntoSystem<P> {
+    type System: System;
+
+    /// Wraps the function as a system called `name`.
+    ///
+    /// Closures defined in the same function share a type name, so the name has to be given.
+    fn into_system<N: Into<SystemId>>(self, name: N) -> SystemWrapper<Self::System>;
+}
+
+macro_rules! impl_function_system {
+    ( $($param:ident),* ) => {
+        #[allow(non_snake_case, unused_variables)]
+        impl<Func, $($param),*> System for FunctionSystem<Func, ($($param,)*)>
+        where
+            Func: FnMut(&mut SubWorld, $($param),*)
+                + for<'w> FnMut(&mut SubWorld, $(SystemParamItem<'w, $param>),*),
+            $( $param: SystemParam ),*
+        {
+            type Data = ($( <$param as SystemParam>::Data, )*);
+
+            fn run(
+                &mut self,
+                ($($param,)*): SystemDataItem<'_, Self::Data>,
+                _command_buffer: &mut CommandBuffer,
+                world: &mut SubWorld,
+            ) {
+                // Calling through a function with a single `FnMut` bound avoids ambiguity between the two above
+                #[allow(clippy::too_many_arguments)]
+                fn call<$($param),*>(
+                    mut function: impl FnMut(&mut SubWorld, $($param),*),
+                    world: &mut SubWorld,
+                    $($param: $param),*
+                ) {
+                    function(world, $($param),*)
+                }
+
+                call(&mut self.function, world, $($param),*)
+            }
+        }
+
+        impl<Func, $($param),*> IntoSystem<($($param,)*)> for Func
+        where
+            Func: FnMut(&mut SubWorld, $($param),*)
+                + for<'w> FnMut(&mut SubWorld, $(SystemParamItem<'w, $param>),*),
+            $( $param: SystemParam ),*
+        {
+            type System = FunctionSystem<Func, ($($param,)*)>;
+
+            fn into_system<N: Into<SystemId>>(self, name: N) -> SystemWrapper<Self::System> {
+                SystemWrapper::named(
+                    name,
+                    FunctionSystem {
+                        function: self,
+                        _params: PhantomData,
+                    },
+                )
+            }
+        }
+    };
+}
+
+mod impl_function_system {
+    #![cfg_attr(rustfmt, rustfmt_skip)]
+
+    use super::*;
+
+    impl_function_system!();
+    impl_function_system!(A);
+    impl_function_system!(A, B);
+    impl_function_system!(A, B, C);
+    impl_function_system!(A, B, C, D);
+    impl_function_system!(A, B, C, D, E);
+    impl_function_system!(A, B, C, D, E, F);
+    impl_function_system!(A, B, C, D, E, F, G);
+    impl_function_system!(A, B, C, D, E, F, G, H);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IntoSystem;
+    use crate::ScheduleBuilder;
+    use legion::query::Query;
+    use legion::systems::{Fetch, FetchMut};
+    use legion::world::SubWorld;
+    use legion::{Resources, Universe, Write};
+
+    #[test]
+    fn closures_in_one_function_get_their_own_names() {
+        let first = (|_world: &mut SubWorld| {}).into_system("first");
+        let second = (|_world: &mut SubWorld| {}).into_system("second");
+
+        ScheduleBuilder::new()
+            .add_system(first)
+            .add_system(second)
+            .try_build()
+            .unwrap();
+    }
+
+    struct Position(f32);
+
+    #[derive(Default)]
+    struct Step(f32);
+
+    #[derive(Default)]
+    struct Moved(usize);
+
+    fn advance(
+        world: &mut SubWorld,
+        step: Fetch<Step>,
+        mut moved: FetchMut<Moved>,
+        query: &mut Query<Write<Position>>,
+    ) {
+        for position in query.iter_mut(world) {
+            position.0 += step.0;
+            moved.0 += 1;
+        }
+    }
+
+    #[test]
+    fn function_systems_fetch_resources_and_queries() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(Step(2.0));
+        resources.insert(Moved::default());
+
+        world.extend(vec![(Position(0.0),), (Position(5.0),)]);
+
+        let mut schedule = ScheduleBuilder::new()
+            .add_system(advance.into_system("advance"))
+            .build();
+        schedule.execute(&mut world, &mut resources);
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(resources.get::<Moved>().unwrap().0, 4);
+        let mut query = Query::<Write<Position>>::new();
+        let positions: Vec<f32> = query
+            .iter_mut(&mut world)
+            .map(|position| position.0)
+            .collect();
+        assert_eq!(positions, [4.0, 9.0]);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::permissions_conflict;
+use legion::storage::ComponentTypeId;
+use legion::systems::{ResourceTypeId, Runnable};
+use legion::world::Permissions;
+use std::fmt::Write;
+
+/// Renders declared access of the systems as a Graphviz DOT graph.
+///
+/// Read edges point from the data to the system, write edges from the system to the data.
+/// Systems which can't run concurrently are connected by red edges. Archetypes are not known
+/// before the schedule runs, so component conflicts are reported even if the queries of both
+/// systems end up matching disjoint archetypes.
+pub fn access_graph(systems: &[&dyn Runnable]) -> String {
+    let mut resources: Vec<ResourceTypeId> = Vec::new();
+    let mut components: Vec<ComponentTypeId> = Vec::new();
+    let mut access = Vec::with_capacity(systems.len());
+
+    for system in systems {
+        let (resource_reads, component_reads) = system.reads();
+        let (resource_writes, component_writes) = system.writes();
+
+        let mut resource_permissions = Permissions::default();
+        let mut component_permissions = Permissions::default();
+        for resource in resource_reads {
+            resource_permissions.push_read(*resource);
+        }
+        for resource in resource_writes {
+            resource_permissions.push(*resource);
+        }
+        for component in component_reads {
+            component_permissions.push_read(*component);
+        }
+        for component in component_writes {
+            component_permissions.push(*component);
+        }
+
+        for resource in resource_reads.iter().chain(resource_writes) {
+            if !resources.contains(resource) {
+                resources.push(*resource);
+            }
+        }
+        for component in component_reads.iter().chain(component_writes) {
+            if !components.contains(component) {
+                components.push(*component);
+            }
+        }
+
+        access.push((resource_permissions, component_permissions));
+    }
+
+    let mut dot = String::new();
+    // Writing into a `String` can't fail.
+    let _ = render(&mut dot, systems, &resources, &components, &access);
+    dot
+}
+
+fn render(
+    dot: &mut String,
+    systems: &[&dyn Runnable],
+    resources: &[ResourceTypeId],
+    components: &[ComponentTypeId],
+    access: &[(Permissions<ResourceTypeId>, Permissions<ComponentTypeId>)],
+) -> std::fmt::Result {
+    writeln!(dot, "digraph systems {{")?;
+
+    for (i, system) in systems.iter().enumerate() {
+        let name = escape(&system.name().to_string());
+        writeln!(dot, "    s{} [label=\"{}\", shape=box];", i, name)?;
+    }
+    for (i, resource) in resources.iter().enumerate() {
+        let name = escape(&format!("{:?}", resource));
+        writeln!(dot, "    r{} [label=\"{}\", shape=ellipse];", i, name)?;
+    }
+    for (i, component) in components.iter().enumerate() {
+        let name = escape(&format!("{:?}", component));
+        writeln!(dot, "    c{} [label=\"{}\", shape=diamond];", i, name)?;
+    }
+
+    for (i, (resource_access, component_access)) in access.iter().enumerate() {
+        for (j, resource) in resources.iter().enumerate() {
+            if resource_access.writes().contains(resource) {
+                writeln!(dot, "    s{} -> r{} [label=\"write\", style=bold];", i, j)?;
+            } else if resource_access.reads().contains(resource) {
+                writeln!(dot, "    r{} -> s{} [label=\"read\"];", j, i)?;
+            }
+        }
+        for (j, component) in components.iter().enumerate() {
+            if component_access.writes().contains(component) {
+                writeln!(dot, "    s{} -> c{} [label=\"write\", style=bold];", i, j)?;
+            } else if component_access.reads().contains(component) {
+                writeln!(dot, "    c{} -> s{} [label=\"read\"];", j, i)?;
+            }
+        }
+    }
+
+    for (i, (first_resources, first_components)) in access.iter().enumerate() {
+        for (j, (second_resources, second_components)) in access.iter().enumerate().skip(i + 1) {
+            if permissions_conflict(first_resources, second_resources)
+                || permissions_conflict(first_components, second_components)
+            {
+                writeln!(
+                    dot,
+                    "    s{} -> s{} [color=red, dir=none, constraint=false];",
+                    i, j
+                )?;
+            }
+        }
+    }
+
+    writeln!(dot, "}}")
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::access_graph;
+    use crate::testing::Probe;
+    use crate::{SetupData, SystemWrapper};
+    use legion::systems::Runnable;
+    use query_proc::query;
+
+    struct Position;
+    struct Velocity;
+
+    fn wrap<D: SetupData>(name: &'static str) -> SystemWrapper<Probe<D>> {
+        SystemWrapper::named(name, Probe::new())
+    }
+
+    #[test]
+    fn conflicting_systems_are_connected_by_red_edges() {
+        let write = wrap::<(query!(mut Position),)>("write_positions");
+        let read = wrap::<(query!(Position),)>("read_positions");
+        let other = wrap::<(query!(Velocity),)>("read_velocities");
+
+        let dot = access_graph(&[&write as &dyn Runnable, &read, &other]);
+
+        assert!(dot.contains("s0 [label=\"write_positions\", shape=box];"));
+        assert!(dot.contains("s2 [label=\"read_velocities\", shape=box];"));
+        let conflicts: Vec<&str> = dot
+            .lines()
+            .filter(|line| line.contains("color=red"))
+            .map(str::trim)
+            .collect();
+        assert_eq!(
+            conflicts,
+            vec!["s0 -> s1 [color=red, dir=none, constraint=false];"]
+        );
+    }
+}
@@ -1,19 +1,68 @@
 use bit_set::BitSet;
-use legion::query::{
-    ComponentFilter, DefaultFilter, EntityFilter, EntityFilterTuple, Passthrough, Query, View,
-};
-use legion::storage::ComponentTypeId;
+use legion::query::{EntityFilter, Query, View};
+use legion::storage::{next_component_version, ComponentTypeId};
 use legion::systems::{
     CommandBuffer, QuerySet, Resource, ResourceSet, ResourceTypeId, Runnable, SystemId, Fetch, FetchMut
 };
 use legion::world::{ArchetypeAccess, ComponentAccess, Permissions, SubWorld, WorldId};
 use legion::*;
-use std::{borrow::Cow, collections::HashMap, marker::PhantomData};
+use query_proc::{query, system, SystemData};
+use std::panic::{self, AssertUnwindSafe};
+use std::{borrow::Cow, collections::HashMap, marker::PhantomData, time::Instant};
+
+/// Emits a `tracing` event at `$level` when the `tracing` feature is enabled.
+macro_rules! log_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
+
+/// Enters an info `tracing` span until the end of the enclosing block when the `tracing` feature is
+/// enabled.
+macro_rules! enter_span {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($($arg)+).entered();
+    };
+}
 
+mod access;
+mod change;
+mod control;
+mod events;
+mod exclusive;
+mod failure;
+mod function_system;
+mod graph;
+mod non_send;
+mod run_criteria;
+mod schedule;
+mod stats;
+#[cfg(test)]
+mod testing;
+mod trace;
+
+use access::*;
+use change::*;
+use control::*;
+use events::*;
+use exclusive::*;
+use failure::*;
+use function_system::*;
+use graph::*;
+use non_send::*;
+use run_criteria::*;
+use schedule::*;
+use stats::*;
+use trace::*;
+
+#[derive(Default)]
 struct TestResourceA {
     a: i32,
 }
 
+#[derive(Default)]
 struct TestResourceB {
     b: i32,
 }
@@ -31,113 +80,495 @@ struct Velocity {
     dy: f32,
 }
 
-fn build_position_update_system() -> impl systems::Schedulable {
-    SystemBuilder::new("update positions")
-        // give it a query - a system may have multiple queries
-        .with_query(<(Write<Position>, Read<Velocity>)>::query())
-        // construct the system
-        .build(|_command_buffer, world, _resources, query| {
-            for (position, velocity) in query.iter_mut(world) {
-                position.x += velocity.dx;
-                position.y += velocity.dy;
+// `query!` expands to nested legion view and filter types, which clippy finds too complex in
+// signatures. Allowed per function so hand-written types elsewhere are still checked.
+#[allow(clippy::type_complexity)]
+fn update_positions(world: &mut SubWorld, query: &mut query!(mut Position, Velocity)) {
+    for (position, velocity) in query.iter_mut(world) {
+        position.x += velocity.dx;
+        position.y += velocity.dy;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn report_moved(world: &mut SubWorld, query: &mut query!(Entity, Position, changed Position)) {
+    for (entity, position) in query.iter(world) {
+        println!("{:?} moved to {:?}", entity, position);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn report_spawned(world: &mut SubWorld, query: &mut query!(Entity, Velocity, added Velocity)) {
+    for (entity, velocity) in query.iter(world) {
+        println!("{:?} spawned with {:?}", entity, velocity);
+    }
+}
+
+/// Sent when two entities come within two units of each other.
+#[derive(Debug)]
+struct Hit {
+    attacker: Entity,
+    target: Entity,
+}
+
+#[allow(clippy::type_complexity)]
+fn detect_collisions(world: &mut SubWorld, query: &mut query!(Entity, Position), mut hits: WriteEvents<Hit>) {
+    let positions: Vec<(Entity, Position)> = query
+        .iter(world)
+        .map(|(entity, position)| (entity, *position))
+        .collect();
+
+    for (i, (attacker, a)) in positions.iter().enumerate() {
+        for (target, b) in &positions[i + 1..] {
+            if (a.x - b.x).powi(2) + (a.y - b.y).powi(2) < 4.0 {
+                hits.send(Hit {
+                    attacker: *attacker,
+                    target: *target,
+                });
             }
-        })
+        }
+    }
+}
+
+fn apply_damage(_world: &mut SubWorld, mut hits: ReadEvents<Hit>) {
+    for hit in hits.iter() {
+        println!("{:?} hit {:?}", hit.attacker, hit.target);
+    }
 }
 
-pub trait SystemData<'a>: Default {
-    type Result: 'a;
+/// Stops position updates while set to `true`.
+#[derive(Default)]
+struct Paused(bool);
 
+struct NotPaused;
+
+impl RunCriteria for NotPaused {
+    type Data = Option<Read<Paused>>;
+
+    fn should_run(&mut self, paused: Option<Fetch<Paused>>) -> bool {
+        !paused.is_some_and(|paused| paused.0)
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn build_position_update_system() -> SystemWrapper<impl System<Data = (query!(mut Position, Velocity),)>> {
+    update_positions.into_system("update positions").run_if(NotPaused)
+}
+
+/// Persistent state of a system parameter, stored inside [`SystemWrapper`] between runs.
+///
+/// The data that a system actually receives is produced by [`SystemDataFetch`] for the
+/// duration of a single run.
+pub trait SystemData: Default + for<'w> SystemDataFetch<'w> {
     fn component_permissions() -> Permissions<ComponentTypeId> {
-        return Permissions::default();
+        Permissions::default()
     }
 
     fn resource_permissions() -> Permissions<ResourceTypeId> {
-        return Permissions::default();
+        Permissions::default()
     }
 
-    fn filter_archetypes(&mut self, world: &World, archetypes: &mut BitSet) {
+    /// Marks archetypes which this data accesses in `archetypes`.
+    fn filter_archetypes(&mut self, _world: &World, _archetypes: &mut BitSet) {}
+
+    /// Lists the access of every parameter contained in this data.
+    fn parameters(parameters: &mut Vec<ParameterAccess>) {
+        parameters.push(ParameterAccess {
+            name: std::any::type_name::<Self>().into(),
+            resources: Self::resource_permissions(),
+            components: Self::component_permissions(),
+            required: Vec::new(),
+            excluded: Vec::new(),
+        });
+    }
+
+    /// Reports every required resource which is missing from `resources`.
+    fn validate(_system: &SystemId, _resources: &Resources, _errors: &mut Vec<SystemDataError>) {}
+
+    /// Fetches the data after checking that every required resource is present.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`SystemDataFetch::fetch_unchecked`].
+    unsafe fn try_fetch<'w>(
+        &'w mut self,
+        system: &SystemId,
+        resources: &'w Resources,
+    ) -> Result<SystemDataItem<'w, Self>, Vec<SystemDataError>> {
+        let mut errors = Vec::new();
+        Self::validate(system, resources, &mut errors);
+
+        if errors.is_empty() {
+            Ok(self.fetch_unchecked(resources))
+        } else {
+            Err(errors)
+        }
     }
+}
 
-    unsafe fn fetch_unchecked(&mut self, resources: &'a Resources) -> &mut Self::Result;
+/// A resource required by a system is missing.
+#[derive(Debug, Clone)]
+pub struct SystemDataError {
+    pub system: SystemId,
+    pub missing: ResourceTypeId,
+    pub type_name: &'static str,
 }
 
-impl<'a> SystemData<'a> for () {
-    type Result = ();
+impl SystemDataError {
+    fn missing<T: Resource>(system: &SystemId) -> Self {
+        Self {
+            system: system.clone(),
+            missing: ResourceTypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+}
 
-    unsafe fn fetch_unchecked(&mut self, resources: &'a Resources) -> &mut Self::Result { &mut () }
+impl std::fmt::Display for SystemDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "system `{}` requires resource `{}` which is not present",
+            self.system, self.type_name
+        )
+    }
 }
 
-impl<'a, V, F> SystemData<'a> for Query<V, F>
+impl std::error::Error for SystemDataError {}
+
+/// Borrows the data required by a single system run.
+pub trait SystemDataFetch<'w> {
+    type Item;
+
+    /// # Safety
+    ///
+    /// The caller must ensure that no other borrows of the resources declared in
+    /// [`SystemData::resource_permissions`] are alive for `'w`.
+    unsafe fn fetch_unchecked(&'w mut self, resources: &'w Resources) -> Self::Item;
+}
+
+/// Data handed to a system for a single run.
+pub type SystemDataItem<'w, D> = <D as SystemDataFetch<'w>>::Item;
+
+/// System data which can initialize the resources it requires before the first run.
+///
+/// Every [`System::Data`] implements it. `Read<T>` and `Write<T>` implement it when `T: Default`,
+/// tuples when all of their elements do. Derived data always implements it and skips fields without
+/// it, so data with resources lacking a `Default` value has to be derived and the resources inserted
+/// by hand.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot set up the resources it requires",
+    note = "resources without a `Default` value can be used through `#[derive(SystemData)]`, which skips them during setup"
+)]
+pub trait SetupData: SystemData {
+    /// Inserts default values for required resources which are not present yet.
+    fn setup(world: &mut World, resources: &mut Resources);
+}
+
+/// Picks [`SetupData::setup`] for a type when it is implemented and skips the type otherwise.
+///
+/// Used by the derive and `#[system]` macros as `(&SetupProbe::<T>::new()).setup(..)`, which only
+/// resolves to [`SetupWithDefaults`] when `T: SetupData`.
+#[doc(hidden)]
+pub struct SetupProbe<T>(PhantomData<T>);
+
+impl<T> SetupProbe<T> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Default for SetupProbe<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[doc(hidden)]
+pub trait SetupWithDefaults {
+    fn setup(&self, world: &mut World, resources: &mut Resources);
+}
+
+impl<T: SetupData> SetupWithDefaults for SetupProbe<T> {
+    fn setup(&self, world: &mut World, resources: &mut Resources) {
+        <T as SetupData>::setup(world, resources);
+    }
+}
+
+#[doc(hidden)]
+pub trait SetupWithoutDefaults {
+    fn setup(&self, world: &mut World, resources: &mut Resources);
+}
+
+impl<T: SystemData> SetupWithoutDefaults for &SetupProbe<T> {
+    fn setup(&self, _world: &mut World, _resources: &mut Resources) {}
+}
+
+fn insert_default<T: Resource + Default>(resources: &mut Resources) {
+    if !resources.contains::<T>() {
+        resources.insert(T::default());
+    }
+}
+
+impl SetupData for () {
+    fn setup(_world: &mut World, _resources: &mut Resources) {}
+}
+
+impl SystemData for () {
+    fn parameters(_parameters: &mut Vec<ParameterAccess>) {}
+}
+
+impl<'w> SystemDataFetch<'w> for () {
+    type Item = ();
+
+    unsafe fn fetch_unchecked(&'w mut self, _resources: &'w Resources) -> Self::Item {}
+}
+
+impl<V, F> SystemData for Query<V, F>
 where
-    V: for<'b> View<'b> + 'a,
-    F: 'static + EntityFilter,
+    V: for<'b> View<'b>,
+    F: 'static + EntityFilter + FilterComponents,
 {
-    type Result = Self;
-
     fn component_permissions() -> Permissions<ComponentTypeId> {
-        return V::requires_permissions();
+        V::requires_permissions()
+    }
+
+    fn parameters(parameters: &mut Vec<ParameterAccess>) {
+        let mut required = Vec::new();
+        let mut excluded = Vec::new();
+        F::components(&mut required, &mut excluded);
+
+        parameters.push(ParameterAccess {
+            name: std::any::type_name::<Self>().into(),
+            resources: Self::resource_permissions(),
+            components: Self::component_permissions(),
+            required,
+            excluded,
+        });
     }
 
-    unsafe fn fetch_unchecked(&mut self, resources: &'a Resources) -> &mut Self::Result {
+    fn filter_archetypes(&mut self, world: &World, archetypes: &mut BitSet) {
+        <Self as QuerySet>::filter_archetypes(self, world, archetypes);
+    }
+}
+
+impl<V, F> SetupData for Query<V, F>
+where
+    V: for<'b> View<'b>,
+    F: 'static + EntityFilter + FilterComponents,
+{
+    fn setup(_world: &mut World, _resources: &mut Resources) {}
+}
+
+impl<'w, V, F> SystemDataFetch<'w> for Query<V, F>
+where
+    V: for<'b> View<'b>,
+    F: 'static + EntityFilter,
+{
+    type Item = &'w mut Self;
+
+    unsafe fn fetch_unchecked(&'w mut self, _resources: &'w Resources) -> Self::Item {
         self
     }
 }
 
-impl<'a, T> SystemData<'a> for Read<T>
+impl<T> SystemData for Read<T>
 where
     T: Resource,
 {
-    type Result = Fetch<'a, T>;
-
     fn resource_permissions() -> Permissions<ResourceTypeId> {
         let mut permissions = Permissions::default();
         permissions.push_read(ResourceTypeId::of::<T>());
-        return permissions;
+        permissions
+    }
+
+    fn validate(system: &SystemId, resources: &Resources, errors: &mut Vec<SystemDataError>) {
+        if !resources.contains::<T>() {
+            errors.push(SystemDataError::missing::<T>(system));
+        }
     }
+}
 
-    unsafe fn fetch_unchecked(&mut self, resources: &'a Resources) -> &mut Self::Result {
-        &mut <Self as ResourceSet<'a>>::fetch_unchecked(resources)
+impl<T> SetupData for Read<T>
+where
+    T: Resource + Default,
+{
+    fn setup(_world: &mut World, resources: &mut Resources) {
+        insert_default::<T>(resources);
     }
 }
 
-impl<'a, T> SystemData<'a> for Write<T>
+impl<'w, T> SystemDataFetch<'w> for Read<T>
 where
     T: Resource,
 {
-    type Result = FetchMut<'a, T>;
+    type Item = Fetch<'w, T>;
+
+    unsafe fn fetch_unchecked(&'w mut self, resources: &'w Resources) -> Self::Item {
+        <Self as ResourceSet<'w>>::fetch_unchecked(resources)
+    }
+}
 
+impl<T> SystemData for Write<T>
+where
+    T: Resource,
+{
     fn resource_permissions() -> Permissions<ResourceTypeId> {
         let mut permissions = Permissions::default();
         permissions.push(ResourceTypeId::of::<T>());
-        return permissions;
+        permissions
     }
 
-    unsafe fn fetch_unchecked(&mut self, resources: &'a Resources) -> &mut Self::Result {
-        &mut <Self as ResourceSet<'a>>::fetch_unchecked(resources)
+    fn validate(system: &SystemId, resources: &Resources, errors: &mut Vec<SystemDataError>) {
+        if !resources.contains::<T>() {
+            errors.push(SystemDataError::missing::<T>(system));
+        }
     }
 }
 
-pub trait System<'a> {
-    type Data: SystemData<'a>;
+impl<T> SetupData for Write<T>
+where
+    T: Resource + Default,
+{
+    fn setup(_world: &mut World, resources: &mut Resources) {
+        insert_default::<T>(resources);
+    }
+}
+
+impl<'w, T> SystemDataFetch<'w> for Write<T>
+where
+    T: Resource,
+{
+    type Item = FetchMut<'w, T>;
+
+    unsafe fn fetch_unchecked(&'w mut self, resources: &'w Resources) -> Self::Item {
+        <Self as ResourceSet<'w>>::fetch_unchecked(resources)
+    }
+}
+
+impl<T> SystemData for Option<Read<T>>
+where
+    T: Resource,
+{
+    fn resource_permissions() -> Permissions<ResourceTypeId> {
+        <Read<T> as SystemData>::resource_permissions()
+    }
+}
+
+// Optional resources stay optional, setup never inserts them.
+impl<T> SetupData for Option<Read<T>>
+where
+    T: Resource,
+{
+    fn setup(_world: &mut World, _resources: &mut Resources) {}
+}
+
+impl<'w, T> SystemDataFetch<'w> for Option<Read<T>>
+where
+    T: Resource,
+{
+    type Item = Option<Fetch<'w, T>>;
+
+    unsafe fn fetch_unchecked(&'w mut self, resources: &'w Resources) -> Self::Item {
+        resources.get::<T>()
+    }
+}
+
+impl<T> SystemData for Option<Write<T>>
+where
+    T: Resource,
+{
+    fn resource_permissions() -> Permissions<ResourceTypeId> {
+        <Write<T> as SystemData>::resource_permissions()
+    }
+}
+
+impl<T> SetupData for Option<Write<T>>
+where
+    T: Resource,
+{
+    fn setup(_world: &mut World, _resources: &mut Resources) {}
+}
+
+impl<'w, T> SystemDataFetch<'w> for Option<Write<T>>
+where
+    T: Resource,
+{
+    type Item = Option<FetchMut<'w, T>>;
+
+    unsafe fn fetch_unchecked(&'w mut self, resources: &'w Resources) -> Self::Item {
+        resources.get_mut::<T>()
+    }
+}
+
+/// Per-system state which persists between runs and is not shared with other systems.
+#[derive(Default)]
+pub struct Local<T>(T);
+
+impl<T> SystemData for Local<T> where T: Default + Send + Sync + 'static {}
+
+impl<T> SetupData for Local<T>
+where
+    T: Default + Send + Sync + 'static,
+{
+    fn setup(_world: &mut World, _resources: &mut Resources) {}
+}
+
+impl<'w, T> SystemDataFetch<'w> for Local<T>
+where
+    T: 'static,
+{
+    type Item = &'w mut T;
+
+    unsafe fn fetch_unchecked(&'w mut self, _resources: &'w Resources) -> Self::Item {
+        &mut self.0
+    }
+}
+
+pub trait System {
+    type Data: SetupData;
 
     fn run(
         &mut self,
-        data: &mut <Self::Data as SystemData<'a>>::Result,
+        data: SystemDataItem<'_, Self::Data>,
         command_buffer: &mut CommandBuffer,
         world: &mut SubWorld,
     );
+
+    /// Prepares the world and resources before the system runs for the first time.
+    ///
+    /// By default runs [`SetupData::setup`] of [`System::Data`].
+    fn setup(&mut self, world: &mut World, resources: &mut Resources) {
+        <Self::Data as SetupData>::setup(world, resources);
+    }
 }
 
-pub struct SystemWrapper<'a, D> {
+pub struct SystemWrapper<S>
+where
+    S: System,
+{
     name: SystemId,
-    data: D,
+    data: S::Data,
     archetypes: ArchetypeAccess,
     access: SystemAccess,
 
     // We pre-allocate a command buffer for ourself. Writes are self-draining so we never have to rellocate.
     command_buffer: HashMap<WorldId, CommandBuffer>,
 
-    system: &'a mut (dyn System<'a, Data = D> + Send + Sync),
+    // Resources are checked once before the first run, afterwards we fetch unchecked.
+    validated: bool,
+
+    // Component version recorded after the last run, `Changed` and `Added` filters in our queries compare against it.
+    last_run: u64,
+
+    // Ordering constraints used when the system is added as part of a `SystemSet`.
+    ordering: SystemOrdering,
+
+    run_criteria: Option<Box<dyn ErasedRunCriteria>>,
+
+    panic_policy: PanicPolicy,
+
+    system: S,
 }
 
 #[derive(Debug, Clone)]
@@ -146,9 +577,9 @@ pub struct SystemAccess {
     components: Permissions<ComponentTypeId>,
 }
 
-impl<'a, D> Runnable for SystemWrapper<'a, D>
+impl<S> Runnable for SystemWrapper<S>
 where
-    D: SystemData<'a>
+    S: System,
 {
     fn name(&self) -> &SystemId {
         &self.name
@@ -156,21 +587,21 @@ where
 
     fn reads(&self) -> (&[ResourceTypeId], &[ComponentTypeId]) {
         (
-            &self.access.resources.reads(),
-            &self.access.components.reads(),
+            self.access.resources.reads(),
+            self.access.components.reads(),
         )
     }
 
     fn writes(&self) -> (&[ResourceTypeId], &[ComponentTypeId]) {
         (
-            &self.access.resources.writes(),
-            &self.access.components.writes(),
+            self.access.resources.writes(),
+            self.access.components.writes(),
         )
     }
 
     fn prepare(&mut self, world: &World) {
         if let ArchetypeAccess::Some(bitset) = &mut self.archetypes {
-            // self.data.filter_archetypes(world, bitset);
+            self.data.filter_archetypes(world, bitset);
         }
     }
 
@@ -179,27 +610,68 @@ where
     }
 
     fn command_buffer_mut(&mut self, world: WorldId) -> Option<&mut CommandBuffer> {
-        self.command_buffer.get_mut(&world)
+        // The executor only asks for the command buffer when flushing it.
+        let cmd = self.command_buffer.get_mut(&world);
+        log_event!(
+            debug,
+            system = %self.name,
+            commands = cmd.as_ref().map_or(0, |cmd| cmd.len()),
+            "Flushing commands"
+        );
+        cmd
     }
 
     unsafe fn run_unsafe(&mut self, world: &World, resources: &Resources) {
-        // let span = span!(Level::INFO, "System", system = %self.name);
-        // let _guard = span.enter();
+        enter_span!(
+            "System",
+            system = %self.name,
+            resource_reads = ?self.access.resources.reads(),
+            resource_writes = ?self.access.resources.writes(),
+            component_reads = ?self.access.components.reads(),
+            component_writes = ?self.access.components.writes()
+        );
+        log_event!(debug, "Initializing");
+
+        let enabled = resources
+            .get::<SystemControl>()
+            .is_none_or(|control| control.is_enabled(&self.name));
 
-        // debug!("Initializing");
+        // safety:
+        // The criteria only reads resources, which are included in the declared access.
+        let should_run = enabled
+            && match &mut self.run_criteria {
+                Some(criteria) => criteria.should_run(&self.name, resources),
+                None => true,
+            };
+
+        if !should_run {
+            log_event!(debug, "Skipped");
+
+            if let Some(stats) = resources.get::<SystemStats>() {
+                stats.record_skipped(&self.name);
+            }
+            return;
+        }
 
         // safety:
-        // It is difficult to correctly communicate the lifetime of the resource fetch through to the system closure.
-        // We are hacking this by passing the fetch with a static lifetime to its internal references.
-        // This is sound because the fetch structs only provide access to the resource through reborrows on &self.
-        // As the fetch struct is created on the stack here, and the resources it is holding onto is a parameter to this function,
-        // we know for certain that the lifetime of the fetch struct (which constrains the lifetime of the resource the system sees)
-        // must be shorter than the lifetime of the resource.
-        let resources_static = std::mem::transmute::<_, &'static Resources>(resources);
-        //let mut resources = R::fetch_unchecked(resources_static);
-        let mut data = self.data.fetch_unchecked(resources_static);
-
-        //let queries = &mut self.queries;
+        // The executor only runs this system when no other system holds conflicting borrows of the
+        // resources and components declared in `self.access`. The fetched data borrows both
+        // `self.data` and `resources` for the duration of this call only, so nothing can outlive them.
+        let data = if self.validated {
+            self.data.fetch_unchecked(resources)
+        } else {
+            match self.data.try_fetch(&self.name, resources) {
+                Ok(data) => {
+                    self.validated = true;
+                    data
+                }
+                Err(errors) => panic!("{}", format_errors(&errors)),
+            }
+        };
+
+        // Change filters in our queries compare against the previous run of this system.
+        let _last_run_guard = LastRunGuard::enter(self.last_run);
+
         let component_access = ComponentAccess::Allow(Cow::Borrowed(&self.access.components));
         let mut world_shim =
             SubWorld::new_unchecked(world, component_access, self.archetypes.bitset());
@@ -208,47 +680,244 @@ where
             .entry(world.id())
             .or_insert_with(|| CommandBuffer::new(world));
 
-        //info!("Running");
-        self.system
-            .run(&mut data, cmd, &mut world_shim);
+        // Counted up front, the world layout cannot change while the system runs.
+        let entities = matched_entities(world, &self.archetypes);
+
+        log_event!(info, "Running");
+        let start = Instant::now();
+        let panic_policy = self.panic_policy;
+        let system = &mut self.system;
+        let result = match panic_policy {
+            PanicPolicy::Propagate => {
+                system.run(data, cmd, &mut world_shim);
+                Ok(())
+            }
+            PanicPolicy::Catch | PanicPolicy::CatchAndDisable => {
+                panic::catch_unwind(AssertUnwindSafe(|| system.run(data, cmd, &mut world_shim)))
+            }
+        };
+        let duration = start.elapsed();
+
+        if let Err(payload) = result {
+            let message = panic_message(&*payload);
+            log_event!(warn, %message, "Panicked");
+
+            // Commands queued before the panic would apply a half finished update.
+            *cmd = CommandBuffer::new(world);
+            if self.panic_policy == PanicPolicy::CatchAndDisable {
+                if let Some(control) = resources.get::<SystemControl>() {
+                    control.disable(self.name.clone());
+                }
+            }
+
+            if let Some(stats) = resources.get::<SystemStats>() {
+                stats.record_failure(&self.name, duration);
+            }
+            if let Some(trace) = resources.get::<TraceRecorder>() {
+                trace.record(&self.name, start, duration);
+            }
+            if let Some(failures) = resources.get::<SystemFailures>() {
+                failures.record(&self.name, message);
+            }
+            return;
+        }
+
+        log_event!(debug, commands = cmd.len(), "Queued commands for the next flush");
+        // Versions written by this run are older, so the next run only sees changes made after it.
+        self.last_run = next_component_version();
+
+        if let Some(stats) = resources.get::<SystemStats>() {
+            stats.record(&self.name, duration, entities);
+        }
+        if let Some(trace) = resources.get::<TraceRecorder>() {
+            trace.record(&self.name, start, duration);
+        }
     }
 }
 
-impl<'a, D> SystemWrapper<'a, D>
+impl<S> SystemWrapper<S>
 where
-    D: SystemData<'a>
+    S: System,
 {
-    fn new(system: &'a mut (dyn System<'a, Data = D> + Send + Sync)) -> Self {
-        Self {
-            name: "test".into(),
-            data: D::default(),
+    /// Wraps the system, naming it after its type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if parameters of the system conflict, see [`SystemWrapper::try_named`].
+    pub fn new(system: S) -> Self {
+        Self::named(std::any::type_name::<S>(), system)
+    }
+
+    /// Wraps the system, rejecting parameters with conflicting borrows.
+    pub fn try_new(system: S) -> Result<Self, AccessConflict> {
+        Self::try_named(std::any::type_name::<S>(), system)
+    }
+
+    /// Wraps the system under the given name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if parameters of the system conflict, see [`SystemWrapper::try_named`].
+    pub fn named<N: Into<SystemId>>(name: N, system: S) -> Self {
+        Self::try_named(name, system).unwrap_or_else(|conflict| panic!("{}", conflict))
+    }
+
+    /// Wraps the system under the given name, rejecting parameters with conflicting borrows.
+    ///
+    /// Parameters conflict when they borrow the same resource, or access the same component and
+    /// their filters don't prove them disjoint, and at least one of them borrows mutably.
+    pub fn try_named<N: Into<SystemId>>(name: N, system: S) -> Result<Self, AccessConflict> {
+        let name: SystemId = name.into();
+
+        let mut parameters = Vec::new();
+        S::Data::parameters(&mut parameters);
+        check_parameters(&name, &parameters)?;
+
+        let mut resources = S::Data::resource_permissions();
+        resources.add(bookkeeping_permissions());
+
+        Ok(Self {
+            name,
+            data: S::Data::default(),
             archetypes: ArchetypeAccess::Some(BitSet::default()),
             access: SystemAccess {
-                resources: D::resource_permissions(),
-                components: D::component_permissions(),
+                resources,
+                components: S::Data::component_permissions(),
             },
             command_buffer: HashMap::default(),
-            system: system,
+            validated: false,
+            last_run: 0,
+            ordering: SystemOrdering::default(),
+            run_criteria: None,
+            panic_policy: PanicPolicy::default(),
+            system,
+        })
+    }
+
+    /// Runs [`System::setup`] of the wrapped system.
+    ///
+    /// Also inserts [`SystemControl`] if the system is disabled through it after a panic.
+    pub fn setup(&mut self, world: &mut World, resources: &mut Resources) {
+        if self.panic_policy == PanicPolicy::CatchAndDisable {
+            insert_default::<SystemControl>(resources);
+        }
+        self.system.setup(world, resources);
+    }
+
+    /// Adds a label which other systems in a [`SystemSet`] can order against.
+    ///
+    /// Every system is implicitly labelled with its name.
+    pub fn label<L: Into<Cow<'static, str>>>(mut self, label: L) -> Self {
+        self.ordering.labels.push(label.into());
+        self
+    }
+
+    /// Runs the system before all systems with the given label.
+    pub fn before<L: Into<Cow<'static, str>>>(mut self, label: L) -> Self {
+        self.ordering.before.push(label.into());
+        self
+    }
+
+    /// Runs the system after all systems with the given label.
+    pub fn after<L: Into<Cow<'static, str>>>(mut self, label: L) -> Self {
+        self.ordering.after.push(label.into());
+        self
+    }
+
+    /// Only runs the system while `criteria` returns `true`.
+    ///
+    /// Skipped runs don't queue any commands and don't count as a run for `Changed` and `Added` filters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the criteria writes resources or accesses components.
+    pub fn run_if<C>(mut self, criteria: C) -> Self
+    where
+        C: RunCriteria + Send + Sync + 'static,
+        C::Data: Send + Sync + 'static,
+    {
+        let criteria = CriteriaWrapper::new(&self.name, criteria);
+        for resource in C::Data::resource_permissions().reads() {
+            self.access.resources.push_read(*resource);
         }
+
+        self.run_criteria = Some(Box::new(criteria));
+        self
+    }
+
+    /// Sets what happens when the system panics, see [`PanicPolicy`].
+    ///
+    /// When a panic is caught, commands queued by the failed run are discarded. Per-system state
+    /// like [`Local`] is kept as the panic left it.
+    pub fn on_panic(mut self, policy: PanicPolicy) -> Self {
+        self.panic_policy = policy;
+        self
     }
+
+    /// Component version recorded after the last completed run, `0` if the system has not run yet.
+    pub fn last_run(&self) -> u64 {
+        self.last_run
+    }
+
+    /// Checks that every resource required by the system is present.
+    pub fn validate(&self, resources: &Resources) -> Result<(), Vec<SystemDataError>> {
+        let mut errors = Vec::new();
+        S::Data::validate(&self.name, resources, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Resources every wrapped system reads to check whether it runs and to record the run.
+fn bookkeeping_permissions() -> Permissions<ResourceTypeId> {
+    let mut permissions = Permissions::default();
+    permissions.push_read(ResourceTypeId::of::<SystemControl>());
+    permissions.push_read(ResourceTypeId::of::<SystemStats>());
+    permissions.push_read(ResourceTypeId::of::<TraceRecorder>());
+    permissions.push_read(ResourceTypeId::of::<SystemFailures>());
+    permissions
+}
+
+fn format_errors(errors: &[SystemDataError]) -> String {
+    errors
+        .iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Default, SystemData)]
+struct TestSystemData {
+    pos: query!(mut Position),
+    posvel: query!(Entity, Velocity, with Position),
+    res_a: Read<TestResourceA>,
+    res_b: Write<TestResourceB>,
+    runs: Local<u32>,
 }
 
 struct TestSystem {}
 
-impl<'a> System<'a> for TestSystem {
-    type Data = (
-        Query<Write<Position>, <Write<Position> as DefaultFilter>::Filter>,
-        Query<(Entity, Read<Velocity>), EntityFilterTuple<ComponentFilter<Position>, Passthrough>>,
-        Read<TestResourceA>,
-        Write<TestResourceB>,
-    );
+impl System for TestSystem {
+    type Data = TestSystemData;
 
     fn run(
         &mut self,
-        (pos, posvel, res_a, res_b): &mut <Self::Data as SystemData>::Result,
+        TestSystemDataItem {
+            pos,
+            posvel,
+            res_a,
+            res_b,
+            runs,
+        }: TestSystemDataItem,
         _command_buffer: &mut CommandBuffer,
         world: &mut SubWorld,
     ) {
+        *runs += 1;
+        println!("Run: {}", runs);
         println!("TestResourceA: {}", res_a.a);
         println!("TestResourceB: {}", res_b.b);
 
@@ -263,15 +932,174 @@ impl<'a> System<'a> for TestSystem {
     }
 }
 
-query_proc::query!();
+#[system]
+fn print_resources(#[resource] a: &TestResourceA, #[state] frames: &mut u32) {
+    *frames += 1;
+    println!("Frame {} res_a: {}", frames, a.a);
+}
+
+// Reference counted handles are not `Send`, so this can only be accessed by thread-local systems.
+#[derive(Default)]
+struct AssetCache {
+    loaded: std::rc::Rc<Vec<&'static str>>,
+}
+
+struct LoadAssets;
+
+impl System for LoadAssets {
+    type Data = NonSendMut<AssetCache>;
+
+    fn run(
+        &mut self,
+        mut cache: SystemDataItem<'_, Self::Data>,
+        _command_buffer: &mut CommandBuffer,
+        _world: &mut SubWorld,
+    ) {
+        std::rc::Rc::make_mut(&mut cache.loaded).push("player.png");
+    }
+}
+
+struct CountAssets;
+
+impl System for CountAssets {
+    type Data = NonSend<AssetCache>;
+
+    fn run(
+        &mut self,
+        cache: SystemDataItem<'_, Self::Data>,
+        _command_buffer: &mut CommandBuffer,
+        _world: &mut SubWorld,
+    ) {
+        println!("Assets loaded: {}", cache.loaded.len());
+    }
+}
+
+/// Number of frames executed so far.
+#[derive(Default)]
+struct Frames(u64);
+
+/// Plain legion system counting frames, scheduled without a [`SystemWrapper`].
+struct CountFrames {
+    name: SystemId,
+    access: SystemAccess,
+    archetypes: ArchetypeAccess,
+}
+
+impl CountFrames {
+    fn new() -> Self {
+        let mut resources = Permissions::default();
+        resources.push_write(ResourceTypeId::of::<Frames>());
+
+        Self {
+            name: "count_frames".into(),
+            access: SystemAccess {
+                resources,
+                components: Permissions::default(),
+            },
+            archetypes: ArchetypeAccess::Some(BitSet::default()),
+        }
+    }
+}
+
+impl Runnable for CountFrames {
+    fn name(&self) -> &SystemId {
+        &self.name
+    }
+
+    fn reads(&self) -> (&[ResourceTypeId], &[ComponentTypeId]) {
+        (
+            self.access.resources.reads(),
+            self.access.components.reads(),
+        )
+    }
+
+    fn writes(&self) -> (&[ResourceTypeId], &[ComponentTypeId]) {
+        (
+            self.access.resources.writes(),
+            self.access.components.writes(),
+        )
+    }
+
+    fn prepare(&mut self, _world: &World) {}
+
+    fn accesses_archetypes(&self) -> &ArchetypeAccess {
+        &self.archetypes
+    }
+
+    unsafe fn run_unsafe(&mut self, _world: &World, resources: &Resources) {
+        if let Some(mut frames) = resources.get_mut::<Frames>() {
+            frames.0 += 1;
+        }
+    }
+
+    fn command_buffer_mut(&mut self, _world: WorldId) -> Option<&mut CommandBuffer> {
+        None
+    }
+}
+
+fn build_schedule(world: &mut World, resources: &mut Resources) -> Schedule {
+    let mut builder = ScheduleBuilder::new();
+    builder
+        .add_system(build_position_update_system())
+        .add_system_set(
+            SystemSet::new()
+                .with_system(print_resources_system().after("test_system"))
+                .with_system(
+                    SystemWrapper::named("test_system", TestSystem {})
+                        .on_panic(PanicPolicy::CatchAndDisable),
+                ),
+        )
+        .add_system(report_moved.into_system("report_moved"))
+        .add_system(report_spawned.into_system("report_spawned"))
+        .add_system(detect_collisions.into_system("detect_collisions"))
+        .add_system(apply_damage.into_system("apply_damage"))
+        .add_data_system(UpdateEvents::<Hit>::new())
+        .add_thread_local_data_system(LoadAssets)
+        .add_thread_local_data_system(CountAssets)
+        .add_exclusive_system_named(
+            "spawn_velocity",
+            |world: &mut World, _resources: &mut Resources| {
+                world.push((Velocity { dx: 1.0, dy: 1.0 },));
+            },
+        )
+        .add_native_system(CountFrames::new())
+        .add_thread_local_fn(|_world: &mut World, resources: &mut Resources| {
+            if let Some(frames) = resources.get::<Frames>() {
+                println!("Finished frame {}", frames.0);
+            }
+        });
+
+    // `--graph <path>` saves the access of all systems as a Graphviz DOT graph.
+    if let Some(path) = flag_value("--graph") {
+        match std::fs::write(&path, builder.access_graph()) {
+            Ok(()) => println!("Saved access graph to {}", path),
+            Err(error) => println!("Failed to save access graph to {}: {}", path, error),
+        }
+    }
+
+    builder.setup(world, resources).build()
+}
+
+/// Returns the argument following `flag` on the command line.
+fn flag_value(flag: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != flag).nth(1)
+}
 
 fn main() {
-    println!("{}", answer());
     let mut resources = Resources::default();
     let mut world = Universe::new().create_world();
 
     resources.insert(TestResourceA { a: 1234 });
-    resources.insert(TestResourceB { b: 4321 });
+    resources.insert(SystemStats::default());
+    resources.insert(SystemControl::default());
+    resources.insert(SystemFailures::default());
+    resources.insert(Frames::default());
+
+    // `--trace <path>` saves the runs of all systems as Chrome trace JSON.
+    let trace_path = flag_value("--trace");
+    if trace_path.is_some() {
+        resources.insert(TraceRecorder::default());
+    }
 
     // or extend via an IntoIterator of tuples to add many at once (this is faster)
     let _entities: &[Entity] = world.extend(vec![
@@ -284,34 +1112,63 @@ fn main() {
         (Velocity { dx: 0.0, dy: 0.0 },)
     ]);
 
-    let mut test_system = TestSystem {};
-    let test_system =
-        unsafe { std::mem::transmute::<_, &'static mut TestSystem>(&mut test_system) };
     println!(
         "Permissions res: {:?}, comp: {:?}",
         <TestSystem as System>::Data::resource_permissions(),
         <TestSystem as System>::Data::component_permissions()
     );
 
+    // `--toggle <name>` disables the named system, queued changes apply at the start of each frame.
+    if let Some(system) = flag_value("--toggle") {
+        resources.get::<SystemControl>().unwrap().toggle(system);
+    }
+
     // construct a schedule (you should do this on init)
-    let mut schedule = Schedule::builder()
-        .add_system(build_position_update_system())
-        .add_system(SystemWrapper::new(test_system))
-        .build();
+    let mut schedule = build_schedule(&mut world, &mut resources);
 
     schedule.execute(&mut world, &mut resources);
 
+    let stats = resources
+        .get::<SystemStats>()
+        .map(|stats| stats.snapshot())
+        .unwrap_or_default();
+    for (system, stat) in stats {
+        println!(
+            "{}: {} runs ({} failed), {:?} last, {:?} mean, {} entities",
+            system,
+            stat.runs,
+            stat.failures,
+            stat.last_duration,
+            stat.mean_duration(),
+            stat.last_entities
+        );
+    }
+
+    let failures = resources
+        .get::<SystemFailures>()
+        .map(|failures| failures.take())
+        .unwrap_or_default();
+    for failure in failures {
+        println!("{}", failure);
+    }
+
+    if let Some(path) = trace_path {
+        let trace = resources.get::<TraceRecorder>().unwrap();
+        match trace.save(&path) {
+            Ok(()) => println!("Saved {} system runs to {}", trace.events().len(), path),
+            Err(error) => println!("Failed to save trace to {}: {}", path, error),
+        }
+    }
+
     // let e: u32 = <Read<Position>>::query();
 }
 
 macro_rules! impl_data {
     ( $($ty:ident),* ) => {
         #[allow(unused_parens, non_snake_case)]
-        impl<'a, $($ty),*> SystemData<'a> for ( $( $ty , )* )
-        where $( $ty : SystemData<'a> ),*
+        impl<$($ty),*> SystemData for ( $( $ty , )* )
+        where $( $ty : SystemData ),*
         {
-            type Result = (&'a mut $( <$ty as SystemData<'a>>::Result, )*);
-
             fn component_permissions() -> Permissions<ComponentTypeId> {
                 let mut a = Permissions::default();
 
@@ -340,8 +1197,33 @@ macro_rules! impl_data {
                 $( $ty.filter_archetypes(world, bitset); )*
             }
 
-            unsafe fn fetch_unchecked(&mut self, resources: &'a Resources) -> &mut Self::Result {
-                ($( $ty::fetch_unchecked(self.$nidx, resources), )*)
+            fn parameters(parameters: &mut Vec<ParameterAccess>) {
+                $( <$ty as SystemData>::parameters(parameters); )*
+            }
+
+            fn validate(system: &SystemId, resources: &Resources, errors: &mut Vec<SystemDataError>) {
+                $( <$ty as SystemData>::validate(system, resources, errors); )*
+            }
+        }
+
+        impl<$($ty),*> SetupData for ( $( $ty , )* )
+        where $( $ty : SetupData ),*
+        {
+            fn setup(world: &mut World, resources: &mut Resources) {
+                $( <$ty as SetupData>::setup(world, resources); )*
+            }
+        }
+
+        #[allow(unused_parens, non_snake_case)]
+        impl<'w, $($ty),*> SystemDataFetch<'w> for ( $( $ty , )* )
+        where $( $ty : SystemDataFetch<'w> ),*
+        {
+            type Item = ($( <$ty as SystemDataFetch<'w>>::Item, )*);
+
+            unsafe fn fetch_unchecked(&'w mut self, resources: &'w Resources) -> Self::Item {
+                let ($($ty,)*) = self;
+
+                ($( $ty.fetch_unchecked(resources), )*)
             }
         }
     };
@@ -356,10 +1238,10 @@ mod impl_data {
     impl_data!(A, B);
     impl_data!(A, B, C);
     impl_data!(A, B, C, D);
-    // impl_data!(A, B, C, D, E);
-    // impl_data!(A, B, C, D, E, F);
-    // impl_data!(A, B, C, D, E, F, G);
-    // impl_data!(A, B, C, D, E, F, G, H);
+    impl_data!(A, B, C, D, E);
+    impl_data!(A, B, C, D, E, F);
+    impl_data!(A, B, C, D, E, F, G);
+    impl_data!(A, B, C, D, E, F, G, H);
     // impl_data!(A, B, C, D, E, F, G, H, I);
     // impl_data!(A, B, C, D, E, F, G, H, I, J);
     // impl_data!(A, B, C, D, E, F, G, H, I, J, K);
@@ -379,3 +1261,101 @@ mod impl_data {
     // impl_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y);
     // impl_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z);
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Local, ScheduleBuilder, System, SystemDataItem};
+    use legion::systems::CommandBuffer;
+    use legion::world::SubWorld;
+    use legion::{Read, Resources, Universe, Write};
+    use query_proc::{query, system};
+
+    struct Position(f32);
+    #[derive(Default)]
+    struct Step(f32);
+    #[derive(Default)]
+    struct Total(f32);
+
+    struct Advance;
+
+    impl System for Advance {
+        type Data = (Read<Step>, Write<Total>, query!(mut Position), Local<u32>);
+
+        fn run(
+            &mut self,
+            (step, mut total, query, frames): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            world: &mut SubWorld,
+        ) {
+            *frames += 1;
+            for position in query.iter_mut(world) {
+                position.0 += step.0 * *frames as f32;
+                total.0 += position.0;
+            }
+        }
+    }
+
+    // Exercises every kind of fetch in one schedule.
+    #[test]
+    fn schedule_fetches_resources_queries_and_locals() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(Step(1.0));
+        resources.insert(Total::default());
+
+        world.extend(vec![(Position(0.0),), (Position(10.0),)]);
+
+        let mut schedule = ScheduleBuilder::new()
+            .add_data_system_named("advance", Advance)
+            .build();
+        schedule.execute(&mut world, &mut resources);
+        schedule.execute(&mut world, &mut resources);
+
+        // Positions are 1 and 11 after the first frame, the local frame count doubles the second step
+        assert_eq!(resources.get::<Total>().unwrap().0, 28.0);
+    }
+
+    struct Spawned;
+    struct Missing;
+
+    #[system]
+    fn every_argument(
+        #[resource] step: Option<&Step>,
+        #[resource] missing: Option<&mut Missing>,
+        #[resource] total: &mut Total,
+        #[state] unchanged: &u32,
+        query: &mut query!(mut Position),
+        command_buffer: &mut CommandBuffer,
+        world: &mut SubWorld,
+    ) {
+        assert!(missing.is_none());
+        assert_eq!(*unchanged, 0);
+
+        for position in query.iter_mut(world) {
+            position.0 += step.map_or(0.0, |step| step.0);
+            total.0 += position.0;
+        }
+        command_buffer.push((Spawned,));
+    }
+
+    #[test]
+    fn system_attribute_passes_every_argument_kind() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(Step(1.0));
+        resources.insert(Total::default());
+
+        world.extend(vec![(Position(0.0),), (Position(10.0),)]);
+
+        let mut schedule = ScheduleBuilder::new()
+            .add_system(every_argument_system())
+            .build();
+        schedule.execute(&mut world, &mut resources);
+        schedule.execute(&mut world, &mut resources);
+
+        // Positions are 1 and 11 after the first frame, 2 and 12 after the second
+        assert_eq!(resources.get::<Total>().unwrap().0, 26.0);
+        // Two positions and one entity spawned through the command buffer per frame
+        assert_eq!(world.len(), 4);
+    }
+}
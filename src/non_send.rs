@@ -0,0 +1,286 @@
+use crate::{SetupData, SystemData, SystemDataError, SystemDataFetch};
+use legion::systems::{Fetch, FetchMut, ResourceSet, ResourceTypeId, SystemId};
+use legion::world::Permissions;
+use legion::{Read, Resources, World, Write};
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::thread::ThreadId;
+
+/// Resource holding a value which is not `Send` or `Sync`.
+///
+/// The value can only be accessed from the thread which inserted it, which should be the thread
+/// calling `Schedule::execute`. Accessing it from any other thread panics. If the cell is dropped
+/// on any other thread, the value is leaked instead of dropped.
+pub struct NonSendCell<T> {
+    owner: ThreadId,
+    value: ManuallyDrop<T>,
+}
+
+// safety:
+// The cell can move between threads and be shared, but the value itself never is. Every access
+// goes through `check_thread`, which panics unless it happens on the owning thread, and `Drop`
+// only drops the value on the owning thread. The remaining contract is on `T` itself: it must not
+// rely on running code on other threads through shared state reachable without the cell, e.g. an
+// `Rc` clone kept outside of it.
+unsafe impl<T> Send for NonSendCell<T> {}
+unsafe impl<T> Sync for NonSendCell<T> {}
+
+impl<T> Drop for NonSendCell<T> {
+    fn drop(&mut self) {
+        // Dropping on another thread would run `T`'s destructor there, leaking is the only safe option.
+        if std::thread::current().id() == self.owner {
+            // safety:
+            // The value is never accessed again after this.
+            unsafe { ManuallyDrop::drop(&mut self.value) }
+        }
+    }
+}
+
+impl<T> NonSendCell<T> {
+    fn new(value: T) -> Self {
+        Self {
+            owner: std::thread::current().id(),
+            value: ManuallyDrop::new(value),
+        }
+    }
+
+    fn check_thread(&self) {
+        if std::thread::current().id() != self.owner {
+            panic!(
+                "non-send resource `{}` accessed from a thread other than the one it was inserted on",
+                std::any::type_name::<T>()
+            );
+        }
+    }
+
+    pub fn get(&self) -> &T {
+        self.check_thread();
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.check_thread();
+        &mut self.value
+    }
+}
+
+/// Inserts a resource which is not `Send` or `Sync`, owned by the current thread.
+pub fn insert_non_send<T: 'static>(resources: &mut Resources, value: T) {
+    resources.insert(NonSendCell::new(value));
+}
+
+/// Immutable access to a non-send resource of type `T`.
+///
+/// Systems using it are neither `Send` nor `Sync` and have to be added with
+/// [`ScheduleBuilder::add_thread_local_data_system`](crate::ScheduleBuilder::add_thread_local_data_system).
+pub struct NonSend<T> {
+    _phantom: PhantomData<*const T>,
+}
+
+impl<T> Default for NonSend<T> {
+    fn default() -> Self {
+        Self {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: 'static> SystemData for NonSend<T> {
+    fn resource_permissions() -> Permissions<ResourceTypeId> {
+        <Read<NonSendCell<T>> as SystemData>::resource_permissions()
+    }
+
+    fn validate(system: &SystemId, resources: &Resources, errors: &mut Vec<SystemDataError>) {
+        <Read<NonSendCell<T>> as SystemData>::validate(system, resources, errors)
+    }
+}
+
+impl<T: Default + 'static> SetupData for NonSend<T> {
+    fn setup(_world: &mut World, resources: &mut Resources) {
+        if !resources.contains::<NonSendCell<T>>() {
+            insert_non_send(resources, T::default());
+        }
+    }
+}
+
+impl<'w, T: 'static> SystemDataFetch<'w> for NonSend<T> {
+    type Item = NonSendRef<'w, T>;
+
+    unsafe fn fetch_unchecked(&'w mut self, resources: &'w Resources) -> Self::Item {
+        NonSendRef {
+            cell: <Read<NonSendCell<T>> as ResourceSet<'w>>::fetch_unchecked(resources),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Mutable access to a non-send resource of type `T`, see [`NonSend`].
+pub struct NonSendMut<T> {
+    _phantom: PhantomData<*const T>,
+}
+
+impl<T> Default for NonSendMut<T> {
+    fn default() -> Self {
+        Self {
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: 'static> SystemData for NonSendMut<T> {
+    fn resource_permissions() -> Permissions<ResourceTypeId> {
+        <Write<NonSendCell<T>> as SystemData>::resource_permissions()
+    }
+
+    fn validate(system: &SystemId, resources: &Resources, errors: &mut Vec<SystemDataError>) {
+        <Write<NonSendCell<T>> as SystemData>::validate(system, resources, errors)
+    }
+}
+
+impl<T: Default + 'static> SetupData for NonSendMut<T> {
+    fn setup(world: &mut World, resources: &mut Resources) {
+        <NonSend<T> as SetupData>::setup(world, resources);
+    }
+}
+
+impl<'w, T: 'static> SystemDataFetch<'w> for NonSendMut<T> {
+    type Item = NonSendRefMut<'w, T>;
+
+    unsafe fn fetch_unchecked(&'w mut self, resources: &'w Resources) -> Self::Item {
+        NonSendRefMut {
+            cell: <Write<NonSendCell<T>> as ResourceSet<'w>>::fetch_unchecked(resources),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Borrowed non-send resource, the data of [`NonSend`].
+pub struct NonSendRef<'w, T> {
+    cell: Fetch<'w, NonSendCell<T>>,
+    _phantom: PhantomData<*const T>,
+}
+
+impl<'w, T> Deref for NonSendRef<'w, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.cell.get()
+    }
+}
+
+/// Mutably borrowed non-send resource, the data of [`NonSendMut`].
+pub struct NonSendRefMut<'w, T> {
+    cell: FetchMut<'w, NonSendCell<T>>,
+    _phantom: PhantomData<*const T>,
+}
+
+impl<'w, T> Deref for NonSendRefMut<'w, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.cell.get()
+    }
+}
+
+impl<'w, T> DerefMut for NonSendRefMut<'w, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.cell.get_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insert_non_send, NonSend, NonSendCell, NonSendMut};
+    use crate::{panic_message, ScheduleBuilder, System, SystemDataItem};
+    use legion::systems::CommandBuffer;
+    use legion::world::SubWorld;
+    use legion::{Resources, Universe, Write};
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    struct Tracked;
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            DROPPED.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn value_is_only_dropped_on_owning_thread() {
+        let foreign = NonSendCell::new(Tracked);
+        std::thread::spawn(move || drop(foreign)).join().unwrap();
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 0);
+
+        drop(NonSendCell::new(Tracked));
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+    }
+
+    struct Append;
+
+    impl System for Append {
+        type Data = NonSendMut<Rc<Vec<u32>>>;
+
+        fn run(
+            &mut self,
+            mut values: SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            let next = values.len() as u32;
+            Rc::make_mut(&mut values).push(next);
+        }
+    }
+
+    #[derive(Default)]
+    struct Sum(u32);
+
+    struct Total;
+
+    impl System for Total {
+        type Data = (NonSend<Rc<Vec<u32>>>, Write<Sum>);
+
+        fn run(
+            &mut self,
+            (values, mut sum): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            sum.0 = values.iter().sum();
+        }
+    }
+
+    #[test]
+    fn thread_local_systems_access_non_send_resources() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        insert_non_send(&mut resources, Rc::new(vec![10u32]));
+
+        let mut builder = ScheduleBuilder::new();
+        builder
+            .add_thread_local_data_system(Append)
+            .add_thread_local_data_system(Total)
+            .setup(&mut world, &mut resources);
+        let mut schedule = builder.build();
+
+        schedule.execute(&mut world, &mut resources);
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(resources.get::<Sum>().unwrap().0, 13);
+    }
+
+    #[test]
+    fn access_from_other_thread_panics() {
+        let cell = NonSendCell::new(Rc::new(1));
+
+        let payload = std::thread::scope(|scope| scope.spawn(|| **cell.get()).join()).unwrap_err();
+
+        assert_eq!(
+            panic_message(&*payload),
+            "non-send resource `alloc::rc::Rc<i32>` accessed from a thread other than the one it was inserted on"
+        );
+    }
+}
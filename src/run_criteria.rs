@@ -0,0 +1,189 @@
+use crate::{format_errors, SystemData, SystemDataFetch, SystemDataItem};
+use legion::systems::SystemId;
+use legion::Resources;
+
+/// Decides whether a wrapped system runs, see [`SystemWrapper::run_if`](crate::SystemWrapper::run_if).
+///
+/// The data is fetched before the system itself and must only read resources, queries are
+/// not available.
+pub trait RunCriteria {
+    type Data: SystemData;
+
+    fn should_run(&mut self, data: SystemDataItem<'_, Self::Data>) -> bool;
+}
+
+/// Type erased [`RunCriteria`] stored in a [`SystemWrapper`](crate::SystemWrapper).
+pub trait ErasedRunCriteria: Send + Sync {
+    /// # Safety
+    ///
+    /// Resources read by the criteria must not be borrowed mutably elsewhere.
+    unsafe fn should_run(&mut self, system: &SystemId, resources: &Resources) -> bool;
+}
+
+pub struct CriteriaWrapper<C>
+where
+    C: RunCriteria,
+{
+    data: C::Data,
+    validated: bool,
+    criteria: C,
+}
+
+impl<C> CriteriaWrapper<C>
+where
+    C: RunCriteria,
+{
+    /// # Panics
+    ///
+    /// Panics if the criteria data writes resources or accesses components.
+    pub fn new(system: &SystemId, criteria: C) -> Self {
+        let components = C::Data::component_permissions();
+        if !C::Data::resource_permissions().writes().is_empty()
+            || !components.reads().is_empty()
+            || !components.writes().is_empty()
+        {
+            panic!(
+                "run criteria `{}` of system `{}` must only read resources",
+                std::any::type_name::<C>(),
+                system
+            );
+        }
+
+        Self {
+            data: C::Data::default(),
+            validated: false,
+            criteria,
+        }
+    }
+}
+
+impl<C> ErasedRunCriteria for CriteriaWrapper<C>
+where
+    C: RunCriteria + Send + Sync,
+    C::Data: Send + Sync,
+{
+    unsafe fn should_run(&mut self, system: &SystemId, resources: &Resources) -> bool {
+        let data = if self.validated {
+            self.data.fetch_unchecked(resources)
+        } else {
+            match self.data.try_fetch(system, resources) {
+                Ok(data) => {
+                    self.validated = true;
+                    data
+                }
+                Err(errors) => panic!("{}", format_errors(&errors)),
+            }
+        };
+
+        self.criteria.should_run(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RunCriteria;
+    use crate::{ScheduleBuilder, System, SystemDataItem, SystemStats, SystemWrapper};
+    use legion::systems::{CommandBuffer, Fetch};
+    use legion::world::SubWorld;
+    use legion::{Read, Resources, Universe, Write};
+    use query_proc::query;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct Paused(bool);
+
+    #[derive(Default)]
+    struct Runs(usize);
+
+    struct Spawned;
+
+    struct NotPaused;
+
+    impl RunCriteria for NotPaused {
+        type Data = Read<Paused>;
+
+        fn should_run(&mut self, paused: Fetch<'_, Paused>) -> bool {
+            !paused.0
+        }
+    }
+
+    struct Spawn;
+
+    impl System for Spawn {
+        type Data = (Write<Runs>,);
+
+        fn run(
+            &mut self,
+            (mut runs,): SystemDataItem<'_, Self::Data>,
+            command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            runs.0 += 1;
+            command_buffer.push((Spawned,));
+        }
+    }
+
+    #[test]
+    fn system_is_skipped_while_criteria_fails() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(SystemStats::default());
+        resources.insert(Paused(true));
+
+        let mut builder = ScheduleBuilder::new();
+        builder.add_system(SystemWrapper::named("criteria_test::spawn", Spawn).run_if(NotPaused));
+        builder.setup(&mut world, &mut resources);
+        let mut schedule = builder.build();
+
+        schedule.execute(&mut world, &mut resources);
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(resources.get::<Runs>().unwrap().0, 0);
+        assert!(world.is_empty());
+
+        resources.get_mut::<Paused>().unwrap().0 = false;
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(resources.get::<Runs>().unwrap().0, 1);
+        assert_eq!(world.len(), 1);
+
+        let stats: HashMap<_, _> = resources
+            .get::<SystemStats>()
+            .unwrap()
+            .snapshot()
+            .into_iter()
+            .collect();
+        let stat = &stats[&"criteria_test::spawn".into()];
+        assert_eq!((stat.runs, stat.skipped), (1, 2));
+    }
+
+    struct WritesPaused;
+
+    impl RunCriteria for WritesPaused {
+        type Data = Write<Paused>;
+
+        fn should_run(&mut self, _paused: SystemDataItem<'_, Self::Data>) -> bool {
+            true
+        }
+    }
+
+    struct QueriesSpawned;
+
+    impl RunCriteria for QueriesSpawned {
+        type Data = query!(Spawned);
+
+        fn should_run(&mut self, _query: SystemDataItem<'_, Self::Data>) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "must only read resources")]
+    fn criteria_writing_resources_is_rejected() {
+        let _ = SystemWrapper::named("criteria_test::writes", Spawn).run_if(WritesPaused);
+    }
+
+    #[test]
+    #[should_panic(expected = "must only read resources")]
+    fn criteria_accessing_components_is_rejected() {
+        let _ = SystemWrapper::named("criteria_test::queries", Spawn).run_if(QueriesSpawned);
+    }
+}
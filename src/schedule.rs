@@ -0,0 +1,885 @@
+use crate::{
+    access_graph, apply_system_control, AccessConflict, ExclusiveSystem, ExclusiveWrapper, System,
+    SystemWrapper,
+};
+use legion::systems::{Builder, Runnable, Schedulable, SystemId};
+use legion::{Resources, Schedule, World};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+/// Two systems added to the same schedule share a name.
+#[derive(Debug, Clone)]
+pub struct DuplicateSystemName {
+    pub name: SystemId,
+}
+
+impl std::fmt::Display for DuplicateSystemName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "a system named `{}` is added to the schedule more than once",
+            self.name
+        )
+    }
+}
+
+impl std::error::Error for DuplicateSystemName {}
+
+/// Error returned when adding a wrapped system to a schedule.
+#[derive(Debug, Clone)]
+pub enum ScheduleError {
+    Access(AccessConflict),
+    DuplicateName(DuplicateSystemName),
+    /// A system is ordered against a label which no system in the set carries.
+    UnknownLabel {
+        system: SystemId,
+        label: Cow<'static, str>,
+    },
+    /// Ordering constraints form a cycle, listed in running order starting with the system added
+    /// first, which is repeated at the end.
+    Cycle(Vec<SystemId>),
+}
+
+impl std::fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleError::Access(error) => error.fmt(f),
+            ScheduleError::DuplicateName(error) => error.fmt(f),
+            ScheduleError::UnknownLabel { system, label } => write!(
+                f,
+                "system `{}` is ordered against unknown label `{}`",
+                system, label
+            ),
+            ScheduleError::Cycle(systems) => write!(
+                f,
+                "systems form an ordering cycle: {}",
+                systems
+                    .iter()
+                    .map(|system| format!("`{}`", system))
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+impl From<AccessConflict> for ScheduleError {
+    fn from(error: AccessConflict) -> Self {
+        ScheduleError::Access(error)
+    }
+}
+
+impl From<DuplicateSystemName> for ScheduleError {
+    fn from(error: DuplicateSystemName) -> Self {
+        ScheduleError::DuplicateName(error)
+    }
+}
+
+/// Labels and ordering constraints of a wrapped system.
+#[derive(Debug, Clone, Default)]
+pub struct SystemOrdering {
+    pub labels: Vec<Cow<'static, str>>,
+    pub before: Vec<Cow<'static, str>>,
+    pub after: Vec<Cow<'static, str>>,
+}
+
+/// Type erased step stored in a [`ScheduleBuilder`] until the schedule is built.
+trait BuilderStep {
+    /// Name of the system added by this step, if it adds one.
+    fn name(&self) -> Option<&SystemId>;
+    /// Wrapped system added by this step, if any.
+    fn runnable(&self) -> Option<&dyn Runnable> {
+        None
+    }
+    fn setup(&mut self, _world: &mut World, _resources: &mut Resources) {}
+    fn add_to(self: Box<Self>, builder: &mut Builder);
+}
+
+impl<S> BuilderStep for SystemWrapper<S>
+where
+    S: System,
+    SystemWrapper<S>: Schedulable + 'static,
+{
+    fn name(&self) -> Option<&SystemId> {
+        Some(Runnable::name(self))
+    }
+
+    fn runnable(&self) -> Option<&dyn Runnable> {
+        Some(self)
+    }
+
+    fn setup(&mut self, world: &mut World, resources: &mut Resources) {
+        SystemWrapper::setup(self, world, resources);
+    }
+
+    fn add_to(self: Box<Self>, builder: &mut Builder) {
+        builder.add_system(*self);
+    }
+}
+
+/// Wrapped system running on the thread which executes the schedule.
+struct ThreadLocal<S: System>(SystemWrapper<S>);
+
+impl<S> BuilderStep for ThreadLocal<S>
+where
+    S: System,
+    SystemWrapper<S>: Runnable + 'static,
+{
+    fn name(&self) -> Option<&SystemId> {
+        Some(Runnable::name(&self.0))
+    }
+
+    fn runnable(&self) -> Option<&dyn Runnable> {
+        Some(&self.0)
+    }
+
+    fn setup(&mut self, world: &mut World, resources: &mut Resources) {
+        self.0.setup(world, resources);
+    }
+
+    fn add_to(self: Box<Self>, builder: &mut Builder) {
+        builder.add_thread_local(self.0);
+    }
+}
+
+impl<S> BuilderStep for ExclusiveWrapper<S>
+where
+    S: ExclusiveSystem + 'static,
+{
+    fn name(&self) -> Option<&SystemId> {
+        Some(ExclusiveWrapper::name(self))
+    }
+
+    fn add_to(mut self: Box<Self>, builder: &mut Builder) {
+        // Thread-local functions wait for all previously added systems to finish.
+        builder
+            .flush()
+            .add_thread_local_fn(move |world, resources| self.run(world, resources));
+    }
+}
+
+/// Native legion system added without a [`SystemWrapper`].
+struct Native<S>(S);
+
+impl<S: Schedulable + 'static> BuilderStep for Native<S> {
+    fn name(&self) -> Option<&SystemId> {
+        Some(Runnable::name(&self.0))
+    }
+
+    fn runnable(&self) -> Option<&dyn Runnable> {
+        Some(&self.0)
+    }
+
+    fn add_to(self: Box<Self>, builder: &mut Builder) {
+        builder.add_system(self.0);
+    }
+}
+
+/// Function with full access to the world and resources, see [`Builder::add_thread_local_fn`].
+struct ThreadLocalFn<F>(F);
+
+impl<F> BuilderStep for ThreadLocalFn<F>
+where
+    F: FnMut(&mut World, &mut Resources) + 'static,
+{
+    fn name(&self) -> Option<&SystemId> {
+        None
+    }
+
+    fn add_to(self: Box<Self>, builder: &mut Builder) {
+        builder.add_thread_local_fn(self.0);
+    }
+}
+
+/// Waits for all previous systems and applies their command buffers.
+struct Flush;
+
+impl BuilderStep for Flush {
+    fn name(&self) -> Option<&SystemId> {
+        None
+    }
+
+    fn add_to(self: Box<Self>, builder: &mut Builder) {
+        builder.flush();
+    }
+}
+
+/// Type erased [`SystemWrapper`] stored in a [`SystemSet`].
+trait SetSystem {
+    fn name(&self) -> &SystemId;
+    fn ordering(&self) -> &SystemOrdering;
+    fn into_step(self: Box<Self>) -> Box<dyn BuilderStep>;
+}
+
+impl<S> SetSystem for SystemWrapper<S>
+where
+    S: System,
+    SystemWrapper<S>: Schedulable + 'static,
+{
+    fn name(&self) -> &SystemId {
+        Runnable::name(self)
+    }
+
+    fn ordering(&self) -> &SystemOrdering {
+        &self.ordering
+    }
+
+    fn into_step(self: Box<Self>) -> Box<dyn BuilderStep> {
+        self
+    }
+}
+
+/// Group of wrapped systems which are scheduled according to their `before` and `after` constraints.
+///
+/// Systems without constraints between them keep their insertion order.
+#[derive(Default)]
+pub struct SystemSet {
+    systems: Vec<Box<dyn SetSystem>>,
+}
+
+impl SystemSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a wrapped system, see [`SystemWrapper::before`] and [`SystemWrapper::after`].
+    pub fn with_system<S>(mut self, system: SystemWrapper<S>) -> Self
+    where
+        S: System,
+        SystemWrapper<S>: Schedulable + 'static,
+    {
+        self.systems.push(Box::new(system));
+        self
+    }
+
+    /// Sorts systems into layers, each layer only depends on the layers before it.
+    fn layers(&self) -> Result<Vec<Vec<usize>>, ScheduleError> {
+        let mut labelled: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, system) in self.systems.iter().enumerate() {
+            labelled
+                .entry(system.name().to_string())
+                .or_default()
+                .push(i);
+            for label in &system.ordering().labels {
+                labelled.entry(label.to_string()).or_default().push(i);
+            }
+        }
+
+        let mut successors = vec![Vec::new(); self.systems.len()];
+        let mut predecessors = vec![Vec::new(); self.systems.len()];
+        for (i, system) in self.systems.iter().enumerate() {
+            let ordering = system.ordering();
+            let constraints = ordering
+                .before
+                .iter()
+                .map(|label| (label, true))
+                .chain(ordering.after.iter().map(|label| (label, false)));
+
+            for (label, before) in constraints {
+                let others =
+                    labelled
+                        .get(label.as_ref())
+                        .ok_or_else(|| ScheduleError::UnknownLabel {
+                            system: system.name().clone(),
+                            label: label.clone(),
+                        })?;
+
+                for &j in others.iter().filter(|&&j| j != i) {
+                    let (first, second) = if before { (i, j) } else { (j, i) };
+                    successors[first].push(second);
+                    predecessors[second].push(first);
+                }
+            }
+        }
+
+        let mut remaining: Vec<usize> = predecessors.iter().map(Vec::len).collect();
+        let mut placed = vec![false; self.systems.len()];
+        let mut layers = Vec::new();
+        let mut layer: Vec<usize> = (0..self.systems.len())
+            .filter(|&i| remaining[i] == 0)
+            .collect();
+
+        while !layer.is_empty() {
+            let mut next = Vec::new();
+            for &i in &layer {
+                placed[i] = true;
+                for &j in &successors[i] {
+                    remaining[j] -= 1;
+                    if remaining[j] == 0 {
+                        next.push(j);
+                    }
+                }
+            }
+            next.sort_unstable();
+            layers.push(std::mem::replace(&mut layer, next));
+        }
+
+        match placed.iter().position(|placed| !placed) {
+            Some(start) => Err(ScheduleError::Cycle(self.find_cycle(
+                start,
+                &predecessors,
+                &placed,
+            ))),
+            None => Ok(layers),
+        }
+    }
+
+    /// Walks unplaced predecessors from `start` until a system repeats.
+    ///
+    /// Every unplaced system has an unplaced predecessor, so the walk always ends in a cycle.
+    fn find_cycle(
+        &self,
+        start: usize,
+        predecessors: &[Vec<usize>],
+        placed: &[bool],
+    ) -> Vec<SystemId> {
+        let mut path = vec![start];
+        let mut current = start;
+        loop {
+            current = predecessors[current]
+                .iter()
+                .copied()
+                .find(|&i| !placed[i])
+                .expect("unplaced system without unplaced predecessors");
+
+            if let Some(position) = path.iter().position(|&i| i == current) {
+                let mut cycle: Vec<usize> = path[position..].iter().rev().copied().collect();
+                // Start with the system added first, so the same cycle is always reported the same way.
+                let first = (0..cycle.len()).min_by_key(|&i| cycle[i]).unwrap();
+                cycle.rotate_left(first);
+                cycle.push(cycle[0]);
+                return cycle
+                    .into_iter()
+                    .map(|i| self.systems[i].name().clone())
+                    .collect();
+            }
+
+            path.push(current);
+        }
+    }
+}
+
+/// Builds a legion [`Schedule`] out of wrapped systems.
+///
+/// Native legion systems and thread-local functions can be mixed in through
+/// [`ScheduleBuilder::add_native_system`] and [`ScheduleBuilder::add_thread_local_fn`].
+///
+/// Every system added to the builder must have a unique name. Names are only compared within one
+/// builder, so separate schedules can reuse them. Errors are collected while systems are added and
+/// reported by [`ScheduleBuilder::build`].
+///
+/// Built schedules start every frame by applying changes queued in [`SystemControl`](crate::SystemControl).
+#[derive(Default)]
+pub struct ScheduleBuilder {
+    steps: Vec<Box<dyn BuilderStep>>,
+    error: Option<ScheduleError>,
+}
+
+impl ScheduleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push_error(&mut self, error: ScheduleError) {
+        self.error.get_or_insert(error);
+    }
+
+    /// Adds a wrapped system.
+    pub fn add_system<S>(&mut self, system: SystemWrapper<S>) -> &mut Self
+    where
+        S: System,
+        SystemWrapper<S>: Schedulable + 'static,
+    {
+        self.steps.push(Box::new(system));
+        self
+    }
+
+    /// Wraps the system and adds it, named after its type.
+    pub fn add_data_system<S>(&mut self, system: S) -> &mut Self
+    where
+        S: System,
+        SystemWrapper<S>: Schedulable + 'static,
+    {
+        self.add_data_system_named(std::any::type_name::<S>(), system)
+    }
+
+    /// Wraps the system and adds it under the given name.
+    pub fn add_data_system_named<S, N>(&mut self, name: N, system: S) -> &mut Self
+    where
+        S: System,
+        SystemWrapper<S>: Schedulable + 'static,
+        N: Into<SystemId>,
+    {
+        match SystemWrapper::try_named(name, system) {
+            Ok(system) => self.add_system(system),
+            Err(error) => {
+                self.push_error(error.into());
+                self
+            }
+        }
+    }
+
+    /// Adds all systems of the set, flushing between systems which must run in order.
+    pub fn add_system_set(&mut self, set: SystemSet) -> &mut Self {
+        let layers = match set.layers() {
+            Ok(layers) => layers,
+            Err(error) => {
+                self.push_error(error);
+                return self;
+            }
+        };
+
+        let mut systems: Vec<_> = set.systems.into_iter().map(Some).collect();
+        for (i, layer) in layers.iter().enumerate() {
+            // A flush waits for all running systems, so the next layer starts after this one finished.
+            if i > 0 {
+                self.flush();
+            }
+
+            for &index in layer {
+                if let Some(system) = systems[index].take() {
+                    self.steps.push(system.into_step());
+                }
+            }
+        }
+
+        self
+    }
+
+    /// Adds a wrapped system which runs on the thread executing the schedule.
+    ///
+    /// Systems with non-`Send` data, like [`NonSend`](crate::NonSend), can only be added this way.
+    pub fn add_thread_local_system<S>(&mut self, system: SystemWrapper<S>) -> &mut Self
+    where
+        S: System,
+        SystemWrapper<S>: Runnable + 'static,
+    {
+        self.steps.push(Box::new(ThreadLocal(system)));
+        self
+    }
+
+    /// Wraps the system and adds it to run on the thread executing the schedule, named after its type.
+    pub fn add_thread_local_data_system<S>(&mut self, system: S) -> &mut Self
+    where
+        S: System,
+        SystemWrapper<S>: Runnable + 'static,
+    {
+        self.add_thread_local_data_system_named(std::any::type_name::<S>(), system)
+    }
+
+    /// Wraps the system and adds it to run on the thread executing the schedule under the given name.
+    pub fn add_thread_local_data_system_named<S, N>(&mut self, name: N, system: S) -> &mut Self
+    where
+        S: System,
+        SystemWrapper<S>: Runnable + 'static,
+        N: Into<SystemId>,
+    {
+        match SystemWrapper::try_named(name, system) {
+            Ok(system) => self.add_thread_local_system(system),
+            Err(error) => {
+                self.push_error(error.into());
+                self
+            }
+        }
+    }
+
+    /// Adds an exclusive system under the given name.
+    ///
+    /// Command buffers of all previous systems are flushed before it runs.
+    pub fn add_exclusive_system_named<S, N>(&mut self, name: N, system: S) -> &mut Self
+    where
+        S: ExclusiveSystem + 'static,
+        N: Into<SystemId>,
+    {
+        self.steps
+            .push(Box::new(ExclusiveWrapper::named(name, system)));
+        self
+    }
+
+    /// Adds a native legion system, like one built by legion's `SystemBuilder`.
+    ///
+    /// Its name must be unique like that of every other system, but it is not set up, not
+    /// controlled by [`SystemControl`](crate::SystemControl) and does not record statistics.
+    pub fn add_native_system<S>(&mut self, system: S) -> &mut Self
+    where
+        S: Schedulable + 'static,
+    {
+        self.steps.push(Box::new(Native(system)));
+        self
+    }
+
+    /// Adds a function which runs on the thread executing the schedule, once all previously
+    /// added systems finished.
+    pub fn add_thread_local_fn<F>(&mut self, f: F) -> &mut Self
+    where
+        F: FnMut(&mut World, &mut Resources) + 'static,
+    {
+        self.steps.push(Box::new(ThreadLocalFn(f)));
+        self
+    }
+
+    /// Waits for all previously added systems and applies their command buffers.
+    pub fn flush(&mut self) -> &mut Self {
+        self.steps.push(Box::new(Flush));
+        self
+    }
+
+    /// Renders the access of all wrapped systems added so far, see [`access_graph`].
+    ///
+    /// Exclusive systems are left out, they never run alongside other systems.
+    pub fn access_graph(&self) -> String {
+        let systems: Vec<&dyn Runnable> = self
+            .steps
+            .iter()
+            .filter_map(|step| step.runnable())
+            .collect();
+        access_graph(&systems)
+    }
+
+    /// Runs [`SystemWrapper::setup`] of every system added so far.
+    pub fn setup(&mut self, world: &mut World, resources: &mut Resources) -> &mut Self {
+        for step in &mut self.steps {
+            step.setup(world, resources);
+        }
+        self
+    }
+
+    /// Builds the schedule.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a system has conflicting parameters, a name is used more than once, or ordering
+    /// constraints of a [`SystemSet`] refer to an unknown label or form a cycle.
+    pub fn build(&mut self) -> Schedule {
+        self.try_build().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Builds the schedule, returning the first error found while adding systems.
+    ///
+    /// The builder is left empty afterwards, also on error.
+    pub fn try_build(&mut self) -> Result<Schedule, ScheduleError> {
+        let steps = std::mem::take(&mut self.steps);
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        let mut names = HashSet::new();
+        for step in &steps {
+            if let Some(name) = step.name() {
+                if !names.insert(name.clone()) {
+                    return Err(DuplicateSystemName { name: name.clone() }.into());
+                }
+            }
+        }
+
+        let mut builder = Schedule::builder();
+        builder.add_thread_local_fn(apply_system_control);
+        for step in steps {
+            step.add_to(&mut builder);
+        }
+        Ok(builder.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScheduleBuilder, ScheduleError, SystemSet};
+    use crate::{CountFrames, Frames, Local, System, SystemDataItem, SystemWrapper};
+    use legion::systems::CommandBuffer;
+    use legion::world::SubWorld;
+    use legion::{Read, Resources, Universe, World, Write};
+    use query_proc::{query, SystemData};
+
+    struct Noop;
+
+    impl System for Noop {
+        type Data = ();
+
+        fn run(
+            &mut self,
+            _data: SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+        }
+    }
+
+    #[test]
+    fn names_are_unique_per_builder() {
+        for _ in 0..2 {
+            ScheduleBuilder::new()
+                .add_data_system_named("noop", Noop)
+                .add_data_system_named("other", Noop)
+                .try_build()
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let result = ScheduleBuilder::new()
+            .add_data_system_named("noop", Noop)
+            .add_system_set(SystemSet::new().with_system(SystemWrapper::named("noop", Noop)))
+            .try_build();
+
+        match result {
+            Err(ScheduleError::DuplicateName(error)) => assert_eq!(error.name, "noop".into()),
+            _ => panic!("duplicate name was not rejected"),
+        }
+    }
+
+    #[derive(Default)]
+    struct Counter(u32);
+
+    struct Config {
+        step: u32,
+    }
+
+    #[derive(Default, SystemData)]
+    struct CountData {
+        counter: Write<Counter>,
+        config: Read<Config>,
+        runs: Local<u32>,
+    }
+
+    struct Count;
+
+    impl System for Count {
+        type Data = CountData;
+
+        fn run(
+            &mut self,
+            CountDataItem {
+                mut counter,
+                config,
+                runs,
+            }: CountDataItem,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            *runs += 1;
+            counter.0 += config.step;
+        }
+    }
+
+    #[test]
+    fn setup_skips_resources_without_default() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+
+        let mut builder = ScheduleBuilder::new();
+        builder
+            .add_data_system(Count)
+            .setup(&mut world, &mut resources);
+
+        assert!(resources.contains::<Counter>());
+        assert!(!resources.contains::<Config>());
+
+        resources.insert(Config { step: 2 });
+        let mut schedule = builder.build();
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(resources.get::<Counter>().unwrap().0, 2);
+    }
+
+    #[derive(Default)]
+    struct Step(u32);
+
+    struct Advance;
+
+    impl System for Advance {
+        type Data = (Read<Step>, Write<Counter>);
+
+        fn run(
+            &mut self,
+            (step, mut counter): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            counter.0 += step.0 + 1;
+        }
+    }
+
+    #[test]
+    fn setup_inserts_defaults_for_tuple_data() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+
+        let mut builder = ScheduleBuilder::new();
+        builder
+            .add_data_system(Advance)
+            .setup(&mut world, &mut resources);
+
+        assert!(resources.contains::<Step>());
+        assert!(resources.contains::<Counter>());
+
+        let mut schedule = builder.build();
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(resources.get::<Counter>().unwrap().0, 1);
+    }
+
+    #[derive(Default)]
+    struct Order(Vec<&'static str>);
+
+    struct Record(&'static str);
+
+    impl System for Record {
+        type Data = (Write<Order>,);
+
+        fn run(
+            &mut self,
+            (mut order,): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            order.0.push(self.0);
+        }
+    }
+
+    #[test]
+    fn set_orders_systems_by_constraints_before_insertion() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(Order::default());
+
+        let mut schedule = ScheduleBuilder::new()
+            .add_system_set(
+                SystemSet::new()
+                    .with_system(SystemWrapper::named("a", Record("a")).after("late"))
+                    .with_system(SystemWrapper::named("b", Record("b")).label("late"))
+                    .with_system(SystemWrapper::named("c", Record("c")).before("b")),
+            )
+            .build();
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(resources.get::<Order>().unwrap().0, ["c", "b", "a"]);
+    }
+
+    struct Marker;
+
+    struct Spawn;
+
+    impl System for Spawn {
+        type Data = ();
+
+        fn run(
+            &mut self,
+            _data: SystemDataItem<'_, Self::Data>,
+            command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+            command_buffer.push((Marker,));
+        }
+    }
+
+    #[derive(Default)]
+    struct Seen(usize);
+
+    struct CountMarkers;
+
+    impl System for CountMarkers {
+        type Data = (query!(Marker), Write<Seen>);
+
+        fn run(
+            &mut self,
+            (query, mut seen): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            world: &mut SubWorld,
+        ) {
+            seen.0 = query.iter(world).count();
+        }
+    }
+
+    #[test]
+    fn set_flushes_commands_between_dependent_systems() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(Seen::default());
+
+        let mut schedule = ScheduleBuilder::new()
+            .add_system_set(
+                SystemSet::new()
+                    .with_system(SystemWrapper::named("count", CountMarkers).after("spawn"))
+                    .with_system(SystemWrapper::named("spawn", Spawn)),
+            )
+            .build();
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(resources.get::<Seen>().unwrap().0, 1);
+    }
+
+    #[test]
+    fn set_rejects_unknown_labels() {
+        let result = ScheduleBuilder::new()
+            .add_system_set(
+                SystemSet::new().with_system(SystemWrapper::named("a", Noop).after("missing")),
+            )
+            .try_build();
+
+        match result {
+            Err(ScheduleError::UnknownLabel { system, label }) => {
+                assert_eq!(system, "a".into());
+                assert_eq!(label, "missing");
+            }
+            _ => panic!("unknown label was not rejected"),
+        }
+    }
+
+    #[test]
+    fn set_reports_ordering_cycles() {
+        let error = ScheduleBuilder::new()
+            .add_system_set(
+                SystemSet::new()
+                    .with_system(SystemWrapper::named("a", Noop).after("b"))
+                    .with_system(SystemWrapper::named("b", Noop).after("a")),
+            )
+            .try_build()
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "systems form an ordering cycle: `a` -> `b` -> `a`"
+        );
+    }
+
+    #[test]
+    fn native_systems_and_thread_local_fns_run_in_order() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(Frames::default());
+        resources.insert(Order::default());
+
+        let mut schedule = ScheduleBuilder::new()
+            .add_native_system(CountFrames::new())
+            .add_thread_local_fn(|_world: &mut World, resources: &mut Resources| {
+                let frames = resources.get::<Frames>().unwrap().0;
+                resources.get_mut::<Order>().unwrap().0.push(match frames {
+                    1 => "first",
+                    _ => "later",
+                });
+            })
+            .build();
+        schedule.execute(&mut world, &mut resources);
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(resources.get::<Frames>().unwrap().0, 2);
+        assert_eq!(resources.get::<Order>().unwrap().0, ["first", "later"]);
+    }
+
+    #[test]
+    fn native_system_names_must_be_unique() {
+        let result = ScheduleBuilder::new()
+            .add_native_system(CountFrames::new())
+            .add_data_system_named("count_frames", Noop)
+            .try_build();
+
+        match result {
+            Err(ScheduleError::DuplicateName(error)) => {
+                assert_eq!(error.name, "count_frames".into())
+            }
+            _ => panic!("duplicate name was not rejected"),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use legion::systems::SystemId;
+use legion::world::ArchetypeAccess;
+use legion::World;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Statistics of a single wrapped system.
+#[derive(Debug, Clone, Default)]
+pub struct SystemStat {
+    /// Runs including those which panicked.
+    pub runs: u64,
+    /// Runs which panicked and were caught by the [`PanicPolicy`](crate::PanicPolicy) of the system.
+    pub failures: u64,
+    /// Runs skipped by the run criteria of the system or because it was disabled.
+    pub skipped: u64,
+    pub last_duration: Duration,
+    pub max_duration: Duration,
+    pub total_duration: Duration,
+    /// Entities matched by the queries of the system during the last run.
+    ///
+    /// Only the component layout of each query is considered, so entities excluded by `changed` or
+    /// `added` filters or never iterated by the system still count. Entities matched by several
+    /// queries are counted once. Exclusive systems always report 0.
+    pub last_entities: usize,
+    pub total_entities: u64,
+}
+
+impl SystemStat {
+    pub fn mean_duration(&self) -> Duration {
+        if self.runs == 0 {
+            Duration::default()
+        } else {
+            self.total_duration / self.runs as u32
+        }
+    }
+}
+
+/// Resource collecting [`SystemStat`]s of all wrapped systems, keyed by their name.
+///
+/// Every wrapped system declares a read of it, so a system requesting `Write<SystemStats>` waits for
+/// all others. Statistics are only collected while the resource is present.
+#[derive(Debug, Default)]
+pub struct SystemStats {
+    stats: Mutex<HashMap<SystemId, SystemStat>>,
+}
+
+impl SystemStats {
+    /// Returns the statistics of all systems which have run or were skipped.
+    pub fn snapshot(&self) -> Vec<(SystemId, SystemStat)> {
+        self.stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(system, stat)| (system.clone(), stat.clone()))
+            .collect()
+    }
+
+    pub fn record_skipped(&self, system: &SystemId) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.entry(system.clone()).or_default().skipped += 1;
+    }
+
+    pub fn record(&self, system: &SystemId, duration: Duration, entities: usize) {
+        let mut stats = self.stats.lock().unwrap();
+        let stat = stats.entry(system.clone()).or_default();
+
+        stat.runs += 1;
+        stat.last_duration = duration;
+        stat.max_duration = stat.max_duration.max(duration);
+        stat.total_duration += duration;
+        stat.last_entities = entities;
+        stat.total_entities += entities as u64;
+    }
+
+    /// Records a run which panicked, entity counts are left untouched.
+    pub fn record_failure(&self, system: &SystemId, duration: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        let stat = stats.entry(system.clone()).or_default();
+
+        stat.runs += 1;
+        stat.failures += 1;
+        stat.last_duration = duration;
+        stat.max_duration = stat.max_duration.max(duration);
+        stat.total_duration += duration;
+    }
+}
+
+/// Counts the entities in the archetypes a system accesses, which are the archetypes matched by the
+/// layout filters of its queries.
+pub(crate) fn matched_entities(world: &World, archetypes: &ArchetypeAccess) -> usize {
+    let all = world.archetypes();
+    match archetypes {
+        ArchetypeAccess::All => all.iter().map(|archetype| archetype.entities().len()).sum(),
+        ArchetypeAccess::Some(bitset) => {
+            bitset.iter().map(|index| all[index].entities().len()).sum()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SystemStats;
+    use crate::{ScheduleBuilder, System, SystemDataItem};
+    use legion::query::Query;
+    use legion::systems::CommandBuffer;
+    use legion::world::SubWorld;
+    use legion::{Read, Resources, Universe};
+    use query_proc::query;
+    use std::collections::HashMap;
+
+    struct Position(f32);
+    struct Frozen;
+
+    struct Move;
+
+    impl System for Move {
+        type Data = (query!(mut Position, without Frozen),);
+
+        fn run(
+            &mut self,
+            (query,): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            world: &mut SubWorld,
+        ) {
+            query.for_each_mut(world, |position| position.0 += 1.0);
+        }
+    }
+
+    struct Count;
+
+    impl System for Count {
+        type Data = (Query<Read<Position>>, Query<(Read<Position>, Read<Frozen>)>);
+
+        fn run(
+            &mut self,
+            _queries: SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+        }
+    }
+
+    struct Idle;
+
+    impl System for Idle {
+        type Data = ();
+
+        fn run(
+            &mut self,
+            (): SystemDataItem<'_, Self::Data>,
+            _command_buffer: &mut CommandBuffer,
+            _world: &mut SubWorld,
+        ) {
+        }
+    }
+
+    #[test]
+    fn entities_matched_by_queries_are_counted() {
+        let mut world = Universe::new().create_world();
+        let mut resources = Resources::default();
+        resources.insert(SystemStats::default());
+
+        world.extend(vec![(Position(0.0),), (Position(1.0),)]);
+        world.push((Position(2.0), Frozen));
+
+        let mut schedule = ScheduleBuilder::new()
+            .add_data_system_named("moves", Move)
+            .add_data_system_named("counts", Count)
+            .add_data_system_named("idle", Idle)
+            .build();
+        schedule.execute(&mut world, &mut resources);
+
+        let stats: HashMap<_, _> = resources
+            .get::<SystemStats>()
+            .unwrap()
+            .snapshot()
+            .into_iter()
+            .collect();
+        assert_eq!(stats[&"moves".into()].last_entities, 2);
+        // Hand written queries count too, an entity matched by both of them only once.
+        assert_eq!(stats[&"counts".into()].last_entities, 3);
+        assert_eq!(stats[&"idle".into()].last_entities, 0);
+    }
+}
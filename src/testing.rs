@@ -0,0 +1,25 @@
+use crate::{SetupData, System, SystemDataItem};
+use legion::systems::CommandBuffer;
+use legion::world::SubWorld;
+use std::marker::PhantomData;
+
+/// System doing nothing, for tests which only look at the access declared by its data `D`.
+pub struct Probe<D>(PhantomData<D>);
+
+impl<D> Probe<D> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<D: SetupData> System for Probe<D> {
+    type Data = D;
+
+    fn run(
+        &mut self,
+        _data: SystemDataItem<'_, Self::Data>,
+        _command_buffer: &mut CommandBuffer,
+        _world: &mut SubWorld,
+    ) {
+    }
+}
@@ -0,0 +1,200 @@
+use legion::systems::SystemId;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD: u64 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Numeric id of the current thread, stable for the lifetime of the thread.
+fn thread_id() -> u64 {
+    THREAD.with(|thread| *thread)
+}
+
+/// A single run of a system.
+#[derive(Debug, Clone)]
+pub struct TraceEvent {
+    pub system: SystemId,
+    pub thread: u64,
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Default)]
+struct TraceData {
+    events: Vec<TraceEvent>,
+    threads: BTreeMap<u64, String>,
+}
+
+/// Resource recording runs of all wrapped systems, exported in the Chrome Trace Event format.
+///
+/// Runs are only recorded while the resource is present. Wrapped systems record through a declared
+/// read, so exporting the trace from a system through `Read` can run alongside them. Timestamps are
+/// relative to the creation of the recorder.
+#[derive(Debug)]
+pub struct TraceRecorder {
+    epoch: Instant,
+    data: Mutex<TraceData>,
+}
+
+impl Default for TraceRecorder {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            data: Mutex::default(),
+        }
+    }
+}
+
+impl TraceRecorder {
+    pub fn record(&self, system: &SystemId, start: Instant, duration: Duration) {
+        let thread = thread_id();
+        let mut data = self.data.lock().unwrap();
+
+        data.threads.entry(thread).or_insert_with(|| {
+            std::thread::current()
+                .name()
+                .map(str::to_owned)
+                .unwrap_or_else(|| format!("thread {}", thread))
+        });
+        data.events.push(TraceEvent {
+            system: system.clone(),
+            thread,
+            start: start.saturating_duration_since(self.epoch),
+            duration,
+        });
+    }
+
+    /// Returns all recorded events.
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.data.lock().unwrap().events.clone()
+    }
+
+    /// Writes recorded events as Chrome Trace Event JSON, viewable in `chrome://tracing` or Perfetto.
+    pub fn write_chrome_trace<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let data = self.data.lock().unwrap();
+
+        write!(writer, "{{\"traceEvents\":[")?;
+        let mut first = true;
+        for (thread, name) in &data.threads {
+            if !first {
+                write!(writer, ",")?;
+            }
+            first = false;
+            write!(
+                writer,
+                "\n{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+                thread,
+                escape(name)
+            )?;
+        }
+        for event in &data.events {
+            if !first {
+                write!(writer, ",")?;
+            }
+            first = false;
+            write!(
+                writer,
+                "\n{{\"name\":\"{}\",\"cat\":\"system\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{},\"dur\":{}}}",
+                escape(&event.system.to_string()),
+                event.thread,
+                micros(event.start),
+                micros(event.duration)
+            )?;
+        }
+        write!(writer, "\n]}}")?;
+
+        writer.flush()
+    }
+
+    /// Writes recorded events as Chrome Trace Event JSON into a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_chrome_trace(BufWriter::new(File::create(path)?))
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TraceRecorder;
+    use serde_json::Value;
+    use std::time::Duration;
+
+    #[test]
+    fn chrome_trace_contains_runs_and_threads() {
+        let recorder = TraceRecorder::default();
+        recorder.record(
+            &"first".into(),
+            recorder.epoch + Duration::from_micros(250),
+            Duration::from_micros(1500),
+        );
+        std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .name("worker".to_owned())
+                .spawn_scoped(scope, || {
+                    recorder.record(
+                        &"second".into(),
+                        recorder.epoch + Duration::from_millis(2),
+                        Duration::from_micros(40),
+                    )
+                })
+                .unwrap();
+        });
+
+        let mut json = Vec::new();
+        recorder.write_chrome_trace(&mut json).unwrap();
+        let trace: Value = serde_json::from_slice(&json).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+
+        let run = |name: &str| {
+            events
+                .iter()
+                .find(|event| event["ph"] == "X" && event["name"] == name)
+                .unwrap()
+        };
+        let first = run("first");
+        let second = run("second");
+        assert_eq!(first["ts"].as_f64(), Some(250.0));
+        assert_eq!(first["dur"].as_f64(), Some(1500.0));
+        assert_eq!(second["ts"].as_f64(), Some(2000.0));
+        assert_eq!(second["dur"].as_f64(), Some(40.0));
+        assert_ne!(first["tid"], second["tid"]);
+
+        let thread_names: Vec<&Value> = events
+            .iter()
+            .filter(|event| event["ph"] == "M" && event["name"] == "thread_name")
+            .collect();
+        assert_eq!(thread_names.len(), 2);
+        let worker = thread_names
+            .iter()
+            .find(|event| event["args"]["name"] == "worker")
+            .unwrap();
+        assert_eq!(worker["tid"], second["tid"]);
+        assert!(thread_names
+            .iter()
+            .any(|event| event["tid"] == first["tid"]));
+    }
+}